@@ -0,0 +1,194 @@
+//! Price-move circuit breaker.
+//!
+//! The breaker keeps the highest price seen in each of `BREAKER_BUCKETS`
+//! intervals spanning the window, so the reference high rolls forward one
+//! interval at a time instead of resetting when a window ends. A sell that
+//! leaves the price more than `max_drop_bps` below the rolling high trips it,
+//! and further sells are halted until the cooldown passes or the guardian
+//! resumes trading. The tripping sell itself goes through so the trip is
+//! recorded.
+
+use anchor_lang::prelude::*;
+
+use crate::math::{self, Rounding};
+use crate::{ErrorCode, BASIS_POINTS};
+
+/// 20% drop within an hour halts sells for 30 minutes
+pub const DEFAULT_MAX_DROP_BPS: u32 = 20_000;
+pub const DEFAULT_BREAKER_WINDOW: i64 = 60 * 60;
+pub const DEFAULT_BREAKER_COOLDOWN: i64 = 30 * 60;
+/// Intervals the window is split into; the rolling high moves at this resolution
+pub const BREAKER_BUCKETS: usize = 12;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct CircuitBreaker {
+    pub max_drop_bps: u32,         // Out of BASIS_POINTS, 0 disables the breaker
+    pub window: i64,               // Seconds the reference high is kept for
+    pub cooldown: i64,             // Seconds sells stay halted after a trip
+    pub bucket_highs: [u128; BREAKER_BUCKETS], // Highest price per interval, 0 if none seen
+    pub latest_bucket: i64,        // Interval number (time / interval length) of the newest bucket
+    pub halted_until: i64,         // Sells are rejected before this time
+}
+
+impl CircuitBreaker {
+    pub fn new(max_drop_bps: u32, window: i64, cooldown: i64) -> Result<Self> {
+        require!(max_drop_bps < BASIS_POINTS, ErrorCode::InvalidBreakerConfig);
+        require!(window >= BREAKER_BUCKETS as i64 && cooldown >= 0, ErrorCode::InvalidBreakerConfig);
+        Ok(Self { max_drop_bps, window, cooldown, ..Self::default() })
+    }
+
+    pub fn is_halted(&self, now: i64) -> bool {
+        now < self.halted_until
+    }
+
+    /// Seconds per bucket; the buckets together cover at least the window
+    fn bucket_length(&self) -> i64 {
+        let buckets = BREAKER_BUCKETS as i64;
+        ((self.window + buckets - 1) / buckets).max(1)
+    }
+
+    fn slot(bucket: i64) -> usize {
+        bucket.rem_euclid(BREAKER_BUCKETS as i64) as usize
+    }
+
+    /// Move to the bucket `now` falls in, clearing buckets that fell out of the window
+    fn advance(&mut self, now: i64) {
+        let bucket = now / self.bucket_length();
+        if bucket <= self.latest_bucket {
+            return;
+        }
+        let expired = (bucket - self.latest_bucket).min(BREAKER_BUCKETS as i64);
+        for age in 0..expired {
+            self.bucket_highs[Self::slot(bucket - age)] = 0;
+        }
+        self.latest_bucket = bucket;
+    }
+
+    fn record(&mut self, price: u128) {
+        let high = &mut self.bucket_highs[Self::slot(self.latest_bucket)];
+        *high = (*high).max(price);
+    }
+
+    /// Highest price seen over the rolling window
+    pub fn rolling_high(&self) -> u128 {
+        self.bucket_highs.iter().copied().max().unwrap_or_default()
+    }
+
+    /// Track a trade that moved the price from `price_before` to `price`.
+    /// The price before counts towards the high even if it was set before
+    /// the window, so a crash after a quiet spell is still measured from it.
+    /// Returns the reference high if the drop from it trips the breaker.
+    pub fn observe(&mut self, price_before: u128, price: u128, now: i64) -> Result<Option<u128>> {
+        self.advance(now);
+        self.record(price_before);
+        self.record(price);
+        if self.max_drop_bps == 0 {
+            return Ok(None);
+        }
+
+        let high = self.rolling_high();
+        let keep_bps = (BASIS_POINTS - self.max_drop_bps) as u128;
+        let floor = math::mul_div_u128(high, keep_bps, BASIS_POINTS as u128, Rounding::Up)?;
+        if price >= floor {
+            return Ok(None);
+        }
+        self.halted_until = now.saturating_add(self.cooldown);
+        Ok(Some(high))
+    }
+
+    /// Lift a halt and measure further drops from `price`
+    pub fn resume(&mut self, price: u128, now: i64) {
+        self.halted_until = 0;
+        self.bucket_highs = [0; BREAKER_BUCKETS];
+        self.latest_bucket = now / self.bucket_length();
+        self.record(price);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HIGH: u128 = 1_000_000;
+
+    fn breaker() -> CircuitBreaker {
+        let mut breaker = CircuitBreaker::new(DEFAULT_MAX_DROP_BPS, 3_600, 1_800).unwrap();
+        breaker.resume(HIGH, 0);
+        breaker
+    }
+
+    #[test]
+    fn new_rejects_impossible_settings() {
+        assert!(CircuitBreaker::new(BASIS_POINTS, 60, 60).is_err());
+        assert!(CircuitBreaker::new(1_000, 0, 60).is_err());
+        assert!(CircuitBreaker::new(1_000, BREAKER_BUCKETS as i64 - 1, 60).is_err());
+        assert!(CircuitBreaker::new(1_000, 60, -1).is_err());
+        assert!(CircuitBreaker::new(0, 60, 0).is_ok());
+    }
+
+    #[test]
+    fn trips_only_past_the_configured_drop() {
+        let mut breaker = breaker();
+        // Exactly 20% down is still allowed
+        assert_eq!(breaker.observe(HIGH, 800_000, 10).unwrap(), None);
+        assert!(!breaker.is_halted(10));
+
+        assert_eq!(breaker.observe(800_000, 799_999, 20).unwrap(), Some(HIGH));
+        assert!(breaker.is_halted(20));
+        assert!(breaker.is_halted(20 + 1_799));
+        assert!(!breaker.is_halted(20 + 1_800));
+    }
+
+    #[test]
+    fn measures_from_the_window_high() {
+        let mut breaker = breaker();
+        breaker.observe(HIGH, 2 * HIGH, 10).unwrap();
+        // 25% below the new high, though still above the window's opening price
+        assert_eq!(breaker.observe(2 * HIGH, 1_500_000, 20).unwrap(), Some(2 * HIGH));
+    }
+
+    #[test]
+    fn a_crash_on_the_first_trade_of_a_fresh_window_trips() {
+        let mut breaker = breaker();
+        // No trades for longer than the window, then one sell halves the price
+        let now = 3 * 3_600;
+        assert_eq!(breaker.observe(HIGH, HIGH / 2, now).unwrap(), Some(HIGH));
+        assert!(breaker.is_halted(now + 1));
+    }
+
+    #[test]
+    fn the_high_rolls_off_one_bucket_at_a_time() {
+        let bucket = 3_600 / BREAKER_BUCKETS as i64;
+        let mut breaker = breaker();
+        // 15% off the peak early in the window
+        breaker.observe(HIGH, 850_000, bucket).unwrap();
+        assert_eq!(breaker.rolling_high(), HIGH);
+        // A further 7% near the end of the window is measured from the peak...
+        assert_eq!(breaker.observe(850_000, 790_000, 3_600 - 1).unwrap(), Some(HIGH));
+
+        // ...but not once the peak's bucket has rolled off
+        let mut breaker = self::breaker();
+        breaker.observe(HIGH, 850_000, bucket).unwrap();
+        assert_eq!(breaker.observe(850_000, 790_000, 3_600 + 2 * bucket).unwrap(), None);
+        assert_eq!(breaker.rolling_high(), 850_000);
+    }
+
+    #[test]
+    fn disabled_breaker_never_trips() {
+        let mut breaker = CircuitBreaker::new(0, 3_600, 1_800).unwrap();
+        breaker.resume(HIGH, 0);
+        assert_eq!(breaker.observe(HIGH, 1, 10).unwrap(), None);
+        assert!(!breaker.is_halted(10));
+    }
+
+    #[test]
+    fn resume_lifts_the_halt_and_resets_the_reference() {
+        let mut breaker = breaker();
+        breaker.observe(HIGH, 500_000, 10).unwrap();
+        assert!(breaker.is_halted(11));
+
+        breaker.resume(500_000, 11);
+        assert!(!breaker.is_halted(11));
+        assert_eq!(breaker.observe(500_000, 450_000, 12).unwrap(), None);
+    }
+}
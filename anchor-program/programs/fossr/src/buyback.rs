@@ -0,0 +1,123 @@
+//! Buyback-and-burn from surplus vault SOL.
+//!
+//! The vault holds the curve reserve, accrued dev fees and its rent-exempt
+//! minimum; anything beyond that is surplus. The reserve absorbs every lamport
+//! the curve takes, so surplus is funded by setting aside a share of each buy's
+//! dev fee (tracked in `accrued`), plus whatever is sent to the vault directly.
+//! A buyback spends a share of the surplus on the curve without minting the
+//! tokens it buys, so they are burned and the SOL joins the reserve behind the
+//! remaining supply.
+
+use anchor_lang::prelude::*;
+
+use crate::math::{self, Rounding};
+use crate::{ErrorCode, BASIS_POINTS};
+
+/// Buybacks are at most an hour apart unless configured otherwise
+pub const DEFAULT_BUYBACK_INTERVAL: i64 = 60 * 60;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct Buyback {
+    pub share_bps: u32,            // Share of the surplus spent per call (of BASIS_POINTS), 0 disables buybacks
+    pub max_sol: u64,              // Lamports spent per call, 0 for no cap
+    pub min_interval: i64,         // Seconds between calls
+    pub last_run: i64,
+    pub fee_share_bps: u32,        // Share of each buy's dev fee, after the stakers' share, set aside for buybacks
+    pub accrued: u64,              // Lamports set aside from fees and not yet spent
+}
+
+impl Buyback {
+    pub fn new(share_bps: u32, max_sol: u64, min_interval: i64, fee_share_bps: u32) -> Result<Self> {
+        require!(
+            share_bps <= BASIS_POINTS && fee_share_bps <= BASIS_POINTS && min_interval >= 0,
+            ErrorCode::InvalidLimitConfig
+        );
+        Ok(Self { share_bps, max_sol, min_interval, last_run: 0, fee_share_bps, accrued: 0 })
+    }
+
+    pub fn is_due(&self, now: i64) -> bool {
+        now >= self.last_run.saturating_add(self.min_interval)
+    }
+
+    /// Lamports to spend out of `surplus`
+    pub fn amount(&self, surplus: u64) -> Result<u64> {
+        let share = math::mul_div(surplus, self.share_bps as u64, BASIS_POINTS as u64, Rounding::Down)?;
+        Ok(if self.max_sol > 0 { share.min(self.max_sol) } else { share })
+    }
+
+    /// Set aside the buyback's share of `dev_fee`, returning the lamports taken
+    pub fn credit(&mut self, dev_fee: u64) -> Result<u64> {
+        let share = math::mul_div(dev_fee, self.fee_share_bps as u64, BASIS_POINTS as u64, Rounding::Down)?;
+        self.accrued = self.accrued.checked_add(share).ok_or(ErrorCode::ArithmeticOverflow)?;
+        Ok(share)
+    }
+
+    /// Record `sol_amount` spent, drawing on the fees set aside first
+    pub fn spend(&mut self, sol_amount: u64, now: i64) {
+        self.accrued = self.accrued.saturating_sub(sol_amount);
+        self.last_run = now;
+    }
+}
+
+/// Vault lamports not owed to holders, fee recipients, stakers or rent
+pub fn surplus(vault_lamports: u64, rent_floor: u64, sol_reserve: u64, fees_owed: u64) -> u64 {
+    vault_lamports
+        .saturating_sub(rent_floor)
+        .saturating_sub(sol_reserve)
+        .saturating_sub(fees_owed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spends_the_configured_share_up_to_the_cap() {
+        let buyback = Buyback::new(25_000, 0, 60, 0).unwrap();
+        assert_eq!(buyback.amount(1_000_000).unwrap(), 250_000);
+        let capped = Buyback::new(25_000, 100_000, 60, 0).unwrap();
+        assert_eq!(capped.amount(1_000_000).unwrap(), 100_000);
+        assert_eq!(capped.amount(1_000).unwrap(), 250);
+        assert_eq!(Buyback::new(0, 0, 60, 0).unwrap().amount(1_000_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn waits_out_the_minimum_interval() {
+        let mut buyback = Buyback::new(25_000, 0, 60, 0).unwrap();
+        // Never run yet
+        assert!(buyback.is_due(1_000));
+        buyback.last_run = 1_000;
+        assert!(!buyback.is_due(1_059));
+        assert!(buyback.is_due(1_060));
+    }
+
+    #[test]
+    fn sets_aside_its_share_of_dev_fees_until_spent() {
+        let mut buyback = Buyback::new(BASIS_POINTS, 0, 60, 40_000).unwrap();
+        assert_eq!(buyback.credit(1_000).unwrap(), 400);
+        assert_eq!(buyback.credit(999).unwrap(), 399);
+        assert_eq!(buyback.accrued, 799);
+        buyback.spend(500, 1_000);
+        assert_eq!((buyback.accrued, buyback.last_run), (299, 1_000));
+        // Spending donated surplus beyond the fees set aside empties the bucket
+        buyback.spend(1_000, 2_000);
+        assert_eq!(buyback.accrued, 0);
+        // Nothing is set aside unless configured
+        assert_eq!(Buyback::default().credit(1_000).unwrap(), 0);
+    }
+
+    #[test]
+    fn surplus_excludes_everything_the_vault_owes() {
+        assert_eq!(surplus(10_000, 1_000, 5_000, 500), 3_500);
+        // Never negative, even if the vault is short
+        assert_eq!(surplus(5_000, 1_000, 5_000, 500), 0);
+    }
+
+    #[test]
+    fn new_rejects_impossible_settings() {
+        assert!(Buyback::new(BASIS_POINTS + 1, 0, 60, 0).is_err());
+        assert!(Buyback::new(BASIS_POINTS, 0, -1, 0).is_err());
+        assert!(Buyback::new(BASIS_POINTS, 0, 60, BASIS_POINTS + 1).is_err());
+        assert!(Buyback::new(BASIS_POINTS, 0, 0, BASIS_POINTS).is_ok());
+    }
+}
@@ -0,0 +1,660 @@
+//! Bonding curve shapes.
+//!
+//! The shape is chosen once at `initialize` and stored in `ProgramState`. Every
+//! shape implements `BondingCurve`, which fills buys and sells against the
+//! curve's current state and quotes the spot price. Fees are applied by the
+//! caller on top of the curve fill.
+//!
+//! Buys fill at the ask and sells at the bid. Both include the trade's own
+//! price impact, so buying and immediately selling the same tokens can never
+//! return more SOL than was spent, even before fees.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+
+use crate::math::{self, Rounding};
+use crate::{ErrorCode, BASIS_POINTS, PRICE_SCALE};
+
+/// Most price steps a single stepped-curve trade may cross
+pub const MAX_CURVE_STEPS: u32 = 64;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum CurveKind {
+    /// Price moves by `increment` per SOL bought and `decrement` per SOL sold (fixed-point)
+    Linear { increment: u128, decrement: u128 },
+    /// Price compounds by `growth_bps` (out of `BASIS_POINTS`) per SOL traded
+    Exponential { growth_bps: u32 },
+    /// x * y = k over (virtual_sol + reserve) and (virtual_tokens - curve supply)
+    ConstantProduct { virtual_sol: u64, virtual_tokens: u64 },
+    /// Price rises by `step_increment` (fixed-point) every `step_size` atomic units issued
+    Stepped { step_size: u64, step_increment: u128 },
+}
+
+/// What a curve needs to know to price a trade
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CurveState {
+    pub price: u128,
+    pub sol_reserve: u64,
+    pub supply: u64,
+    pub decimals: u8,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CurveFill {
+    /// Tokens (atomic units) for a buy, lamports for a sell
+    pub amount_out: u64,
+    /// Average price the trade filled at: the ask for a buy, the bid for a sell
+    pub fill_price: u128,
+    pub price_after: u128,
+}
+
+pub trait BondingCurve {
+    /// Spot price for `state`
+    fn quote(&self, state: &CurveState) -> Result<u128>;
+    /// Fill a buy of `sol_in` lamports
+    fn buy(&self, state: &CurveState, sol_in: u64) -> Result<CurveFill>;
+    /// Fill a sell of `tokens_in` atomic units
+    fn sell(&self, state: &CurveState, tokens_in: u64) -> Result<CurveFill>;
+}
+
+impl CurveKind {
+    /// Reject parameters under which a shape could pay back more than it
+    /// took on a round trip, however the buys are split
+    pub fn validate(&self) -> Result<()> {
+        let valid = match *self {
+            // Sells must move the price at least as far as buys, or buying in
+            // chunks and selling at once fills above the average ask
+            CurveKind::Linear { increment, decrement } => decrement >= increment,
+            // Buys and sells scale by the same factor per SOL, so a sell
+            // undoes at least the buys it matches
+            CurveKind::Exponential { growth_bps } => growth_bps > 0 && growth_bps <= BASIS_POINTS,
+            // Path-independent as long as both reserves exist; the rounding
+            // in `buy` and `sell` keeps k from shrinking
+            CurveKind::ConstantProduct { virtual_sol, virtual_tokens } => virtual_sol > 0 && virtual_tokens > 0,
+            // Sells walk back down the same steps buys walked up
+            CurveKind::Stepped { step_size, .. } => step_size > 0,
+        };
+        require!(valid, ErrorCode::InvalidCurve);
+        Ok(())
+    }
+
+    fn with_curve<R>(&self, f: impl FnOnce(&dyn BondingCurve) -> R) -> R {
+        match *self {
+            CurveKind::Linear { increment, decrement } => f(&LinearCurve { increment, decrement }),
+            CurveKind::Exponential { growth_bps } => f(&ExponentialCurve { growth_bps }),
+            CurveKind::ConstantProduct { virtual_sol, virtual_tokens } => {
+                f(&ConstantProductCurve { virtual_sol, virtual_tokens })
+            }
+            CurveKind::Stepped { step_size, step_increment } => f(&SteppedCurve { step_size, step_increment }),
+        }
+    }
+}
+
+impl BondingCurve for CurveKind {
+    fn quote(&self, state: &CurveState) -> Result<u128> {
+        self.with_curve(|curve| curve.quote(state))
+    }
+
+    fn buy(&self, state: &CurveState, sol_in: u64) -> Result<CurveFill> {
+        self.with_curve(|curve| curve.buy(state, sol_in))
+    }
+
+    fn sell(&self, state: &CurveState, tokens_in: u64) -> Result<CurveFill> {
+        self.with_curve(|curve| curve.sell(state, tokens_in))
+    }
+}
+
+/// Moves the price by a fixed amount per SOL and fills at the moved price:
+/// buys at the ask above spot, sells at the bid below it
+pub struct LinearCurve {
+    pub increment: u128,
+    pub decrement: u128,
+}
+
+impl BondingCurve for LinearCurve {
+    fn quote(&self, state: &CurveState) -> Result<u128> {
+        Ok(state.price)
+    }
+
+    fn buy(&self, state: &CurveState, sol_in: u64) -> Result<CurveFill> {
+        let increase = math::mul_div_u128(sol_in as u128, self.increment, LAMPORTS_PER_SOL as u128, Rounding::Up)?;
+        let ask = state.price.checked_add(increase).ok_or(ErrorCode::ArithmeticOverflow)?;
+        let amount_out = math::tokens_for_sol(sol_in, ask, state.decimals, Rounding::Down)?;
+        Ok(CurveFill { amount_out, fill_price: ask, price_after: ask })
+    }
+
+    fn sell(&self, state: &CurveState, tokens_in: u64) -> Result<CurveFill> {
+        // The impact is sized by what the tokens are worth at spot
+        let spot_value = math::sol_for_tokens(tokens_in, state.price, state.decimals, Rounding::Up)?;
+        let decrease = math::mul_div_u128(spot_value as u128, self.decrement, LAMPORTS_PER_SOL as u128, Rounding::Up)?;
+        let bid = state.price.saturating_sub(decrease).max(math::min_price());
+        let amount_out = math::sol_for_tokens(tokens_in, bid, state.decimals, Rounding::Down)?;
+        Ok(CurveFill { amount_out, fill_price: bid, price_after: bid })
+    }
+}
+
+/// Scales the price by `1 + growth * SOL traded` and fills at the scaled
+/// price: buys at the ask above spot, sells at the bid below it
+pub struct ExponentialCurve {
+    pub growth_bps: u32,
+}
+
+impl ExponentialCurve {
+    /// Price multiplier for `sol_amount` as a (numerator, denominator) pair
+    fn growth(&self, sol_amount: u64) -> Result<(u128, u128)> {
+        let denominator = (BASIS_POINTS as u128) * (LAMPORTS_PER_SOL as u128);
+        let numerator = (self.growth_bps as u128)
+            .checked_mul(sol_amount as u128)
+            .and_then(|growth| growth.checked_add(denominator))
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        Ok((numerator, denominator))
+    }
+}
+
+impl BondingCurve for ExponentialCurve {
+    fn quote(&self, state: &CurveState) -> Result<u128> {
+        Ok(state.price)
+    }
+
+    fn buy(&self, state: &CurveState, sol_in: u64) -> Result<CurveFill> {
+        let (numerator, denominator) = self.growth(sol_in)?;
+        let ask = math::mul_div_u128(state.price, numerator, denominator, Rounding::Up)?;
+        let amount_out = math::tokens_for_sol(sol_in, ask, state.decimals, Rounding::Down)?;
+        Ok(CurveFill { amount_out, fill_price: ask, price_after: ask })
+    }
+
+    fn sell(&self, state: &CurveState, tokens_in: u64) -> Result<CurveFill> {
+        // The impact is sized by what the tokens are worth at spot
+        let spot_value = math::sol_for_tokens(tokens_in, state.price, state.decimals, Rounding::Up)?;
+        let (numerator, denominator) = self.growth(spot_value)?;
+        let bid = math::mul_div_u128(state.price, denominator, numerator, Rounding::Down)?.max(math::min_price());
+        let amount_out = math::sol_for_tokens(tokens_in, bid, state.decimals, Rounding::Down)?;
+        Ok(CurveFill { amount_out, fill_price: bid, price_after: bid })
+    }
+}
+
+/// Uniswap-style x * y = k with virtual reserves so the curve starts at a
+/// finite price. The stored price is always the spot price of the reserves.
+pub struct ConstantProductCurve {
+    pub virtual_sol: u64,
+    pub virtual_tokens: u64,
+}
+
+impl ConstantProductCurve {
+    /// (SOL, token) reserves the curve prices against
+    fn reserves(&self, state: &CurveState) -> Result<(u128, u128)> {
+        let sol = (self.virtual_sol as u128) + (state.sol_reserve as u128);
+        let tokens = self
+            .virtual_tokens
+            .checked_sub(state.supply)
+            .filter(|tokens| *tokens > 0)
+            .ok_or(ErrorCode::CurveExhausted)?;
+        Ok((sol, tokens as u128))
+    }
+
+    fn spot(sol: u128, tokens: u128, decimals: u8) -> Result<u128> {
+        let scale = math::token_unit(decimals)?
+            .checked_mul(PRICE_SCALE)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        math::mul_div_u128(sol, scale, tokens, Rounding::Down)
+    }
+}
+
+impl BondingCurve for ConstantProductCurve {
+    fn quote(&self, state: &CurveState) -> Result<u128> {
+        let (sol, tokens) = self.reserves(state)?;
+        Self::spot(sol, tokens, state.decimals)
+    }
+
+    fn buy(&self, state: &CurveState, sol_in: u64) -> Result<CurveFill> {
+        let (sol, tokens) = self.reserves(state)?;
+        let k = sol.checked_mul(tokens).ok_or(ErrorCode::ArithmeticOverflow)?;
+        let sol_after = sol + sol_in as u128;
+        // Rounding the remaining tokens up keeps k from shrinking
+        let tokens_after = k.div_ceil(sol_after);
+        let amount_out = math::to_u64(tokens - tokens_after)?;
+        let price_after = Self::spot(sol_after, tokens_after.max(1), state.decimals)?;
+        let fill_price = math::average_price(sol_in, amount_out, state.decimals, Rounding::Up)?;
+        Ok(CurveFill { amount_out, fill_price, price_after })
+    }
+
+    fn sell(&self, state: &CurveState, tokens_in: u64) -> Result<CurveFill> {
+        let (sol, tokens) = self.reserves(state)?;
+        let k = sol.checked_mul(tokens).ok_or(ErrorCode::ArithmeticOverflow)?;
+        let tokens_after = tokens + tokens_in as u128;
+        let sol_after = k.div_ceil(tokens_after);
+        let amount_out = math::to_u64(sol.saturating_sub(sol_after))?;
+        let price_after = Self::spot(sol_after, tokens_after, state.decimals)?;
+        let fill_price = math::average_price(amount_out, tokens_in, state.decimals, Rounding::Down)?;
+        Ok(CurveFill { amount_out, fill_price, price_after })
+    }
+}
+
+/// Flat price within each step of `step_size` tokens, rising by
+/// `step_increment` from one step to the next. Trades that cross steps are
+/// filled step by step.
+pub struct SteppedCurve {
+    pub step_size: u64,
+    pub step_increment: u128,
+}
+
+impl SteppedCurve {
+    fn fill(sol_in: u64, amount_out: u64, price_after: u128, decimals: u8) -> Result<CurveFill> {
+        let fill_price = math::average_price(sol_in, amount_out, decimals, Rounding::Up)?;
+        Ok(CurveFill { amount_out, fill_price, price_after })
+    }
+}
+
+impl BondingCurve for SteppedCurve {
+    fn quote(&self, state: &CurveState) -> Result<u128> {
+        Ok(state.price)
+    }
+
+    fn buy(&self, state: &CurveState, sol_in: u64) -> Result<CurveFill> {
+        let mut price = state.price;
+        let mut room = self.step_size - state.supply % self.step_size;
+        let mut sol_left = sol_in;
+        let mut amount_out: u64 = 0;
+
+        for _ in 0..MAX_CURVE_STEPS {
+            let step_cost = math::sol_for_tokens(room, price, state.decimals, Rounding::Up)?;
+            if sol_left < step_cost {
+                let tokens = math::tokens_for_sol(sol_left, price, state.decimals, Rounding::Down)?;
+                amount_out = amount_out.checked_add(tokens).ok_or(ErrorCode::ArithmeticOverflow)?;
+                return Self::fill(sol_in, amount_out, price, state.decimals);
+            }
+
+            // This step sells out: move to the next one
+            sol_left -= step_cost;
+            amount_out = amount_out.checked_add(room).ok_or(ErrorCode::ArithmeticOverflow)?;
+            price = price.checked_add(self.step_increment).ok_or(ErrorCode::ArithmeticOverflow)?;
+            room = self.step_size;
+            if sol_left == 0 {
+                return Self::fill(sol_in, amount_out, price, state.decimals);
+            }
+        }
+        err!(ErrorCode::CurveStepLimit)
+    }
+
+    fn sell(&self, state: &CurveState, tokens_in: u64) -> Result<CurveFill> {
+        let mut price = state.price;
+        let mut supply_left = state.supply;
+        let mut in_step = state.supply % self.step_size;
+        let mut tokens_left = tokens_in;
+        let mut amount_out: u64 = 0;
+
+        for _ in 0..MAX_CURVE_STEPS {
+            if tokens_left == 0 {
+                break;
+            }
+            if in_step == 0 {
+                if supply_left == 0 {
+                    // Tokens the curve never issued (airdrops) sell at the base price
+                    in_step = tokens_left;
+                } else {
+                    price = price.saturating_sub(self.step_increment).max(math::min_price());
+                    in_step = self.step_size.min(supply_left);
+                }
+            }
+
+            let tokens = in_step.min(tokens_left);
+            let sol = math::sol_for_tokens(tokens, price, state.decimals, Rounding::Down)?;
+            amount_out = amount_out.checked_add(sol).ok_or(ErrorCode::ArithmeticOverflow)?;
+            tokens_left -= tokens;
+            in_step -= tokens;
+            supply_left = supply_left.saturating_sub(tokens);
+        }
+
+        require!(tokens_left == 0, ErrorCode::CurveStepLimit);
+        let fill_price = math::average_price(amount_out, tokens_in, state.decimals, Rounding::Down)?;
+        Ok(CurveFill { amount_out, fill_price, price_after: price })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{MAX_BUY_AMOUNT, MIN_BUY_AMOUNT};
+
+    const DECIMALS: u8 = 9;
+    const TOKEN: u64 = 1_000_000_000;
+
+    fn lamports(price: u64) -> u128 {
+        math::price_from_lamports(price)
+    }
+
+    fn state(price: u128, sol_reserve: u64, supply: u64) -> CurveState {
+        CurveState { price, sol_reserve, supply, decimals: DECIMALS }
+    }
+
+    /// Apply a fill to `state` the way `buy_tokens` does
+    fn after_buy(state: &CurveState, sol_in: u64, fill: &CurveFill) -> CurveState {
+        CurveState {
+            price: fill.price_after,
+            sol_reserve: state.sol_reserve + sol_in,
+            supply: state.supply + fill.amount_out,
+            decimals: state.decimals,
+        }
+    }
+
+    /// Apply a fill to `state` the way `sell_tokens` does
+    fn after_sell(state: &CurveState, tokens_in: u64, fill: &CurveFill) -> CurveState {
+        CurveState {
+            price: fill.price_after,
+            sol_reserve: state.sol_reserve - fill.amount_out,
+            supply: state.supply.saturating_sub(tokens_in),
+            decimals: state.decimals,
+        }
+    }
+
+    fn linear() -> CurveKind {
+        CurveKind::Linear { increment: PRICE_SCALE, decrement: PRICE_SCALE }
+    }
+
+    fn exponential() -> CurveKind {
+        CurveKind::Exponential { growth_bps: 1_000 }
+    }
+
+    fn constant_product() -> CurveKind {
+        CurveKind::ConstantProduct { virtual_sol: 30 * LAMPORTS_PER_SOL, virtual_tokens: 1_000_000_000 * TOKEN }
+    }
+
+    fn stepped() -> CurveKind {
+        CurveKind::Stepped { step_size: 100_000 * TOKEN, step_increment: lamports(100) }
+    }
+
+    #[test]
+    fn validate_rejects_degenerate_parameters() {
+        for curve in [linear(), exponential(), constant_product(), stepped()] {
+            assert!(curve.validate().is_ok());
+        }
+        assert!(CurveKind::Linear { increment: 0, decrement: 0 }.validate().is_ok());
+        assert!(CurveKind::Linear { increment: PRICE_SCALE, decrement: 2 * PRICE_SCALE }.validate().is_ok());
+        assert!(CurveKind::Linear { increment: PRICE_SCALE, decrement: PRICE_SCALE - 1 }.validate().is_err());
+        assert!(CurveKind::Exponential { growth_bps: BASIS_POINTS }.validate().is_ok());
+        assert!(CurveKind::Exponential { growth_bps: 0 }.validate().is_err());
+        assert!(CurveKind::Exponential { growth_bps: BASIS_POINTS + 1 }.validate().is_err());
+        assert!(CurveKind::ConstantProduct { virtual_sol: 0, virtual_tokens: TOKEN }.validate().is_err());
+        assert!(CurveKind::ConstantProduct { virtual_sol: 1, virtual_tokens: 0 }.validate().is_err());
+        assert!(CurveKind::Stepped { step_size: 0, step_increment: 1 }.validate().is_err());
+    }
+
+    #[test]
+    fn every_curve_raises_the_price_on_buys_and_lowers_it_on_sells() {
+        for curve in [linear(), exponential(), constant_product(), stepped()] {
+            let start = state(lamports(10_000), 0, 0);
+            let start = CurveState { price: curve.quote(&start).unwrap(), ..start };
+
+            let mut current = start;
+            let mut bought = 0;
+            for sol_in in [MIN_BUY_AMOUNT, LAMPORTS_PER_SOL, 10 * LAMPORTS_PER_SOL] {
+                let fill = curve.buy(&current, sol_in).unwrap();
+                assert!(fill.amount_out > 0, "{:?}", curve);
+                assert!(fill.price_after >= current.price, "{:?}", curve);
+                bought += fill.amount_out;
+                current = after_buy(&current, sol_in, &fill);
+            }
+            assert!(current.price > start.price, "{:?}", curve);
+
+            let fill = curve.sell(&current, bought).unwrap();
+            assert!(fill.price_after <= current.price, "{:?}", curve);
+        }
+    }
+
+    #[test]
+    fn every_curve_fills_larger_trades_with_more() {
+        for curve in [linear(), exponential(), constant_product(), stepped()] {
+            let start = state(lamports(10_000), 50 * LAMPORTS_PER_SOL, 200_000 * TOKEN);
+            let start = CurveState { price: curve.quote(&start).unwrap(), ..start };
+            let small = curve.buy(&start, LAMPORTS_PER_SOL).unwrap();
+            let large = curve.buy(&start, 2 * LAMPORTS_PER_SOL).unwrap();
+            assert!(large.amount_out > small.amount_out, "{:?}", curve);
+            assert!(large.price_after >= small.price_after, "{:?}", curve);
+
+            let small = curve.sell(&start, 1_000 * TOKEN).unwrap();
+            let large = curve.sell(&start, 2_000 * TOKEN).unwrap();
+            assert!(large.amount_out > small.amount_out, "{:?}", curve);
+            assert!(large.price_after <= small.price_after, "{:?}", curve);
+        }
+    }
+
+    #[test]
+    fn linear_moves_price_proportionally_below_one_lamport() {
+        let curve = linear();
+        let start = state(lamports(10_000), 0, 0);
+        assert_eq!(curve.buy(&start, MIN_BUY_AMOUNT).unwrap().price_after, start.price + PRICE_SCALE / 100);
+        assert_eq!(curve.buy(&start, LAMPORTS_PER_SOL).unwrap().price_after, lamports(10_001));
+        assert_eq!(curve.buy(&start, MAX_BUY_AMOUNT).unwrap().price_after, lamports(10_100));
+        assert_eq!(curve.buy(&start, 1).unwrap().price_after, start.price + 1);
+
+        // 1 token worth 10,000 lamports moves the price by 10,000 / 1e9 and fills at that bid
+        let fill = curve.sell(&start, TOKEN).unwrap();
+        assert_eq!(fill.price_after, start.price - 10_000);
+        assert_eq!(fill.fill_price, fill.price_after);
+        assert_eq!(fill.amount_out, 9_999);
+        assert!(curve.buy(&state(u128::MAX, 0, 0), MIN_BUY_AMOUNT).is_err());
+    }
+
+    #[test]
+    fn every_curve_buys_at_or_above_spot_and_sells_at_or_below_it() {
+        for curve in [linear(), exponential(), constant_product(), stepped()] {
+            let start = state(lamports(10_000), 50 * LAMPORTS_PER_SOL, 200_000 * TOKEN);
+            let spot = curve.quote(&start).unwrap();
+            let start = CurveState { price: spot, ..start };
+            for sol_in in [MIN_BUY_AMOUNT, LAMPORTS_PER_SOL, 10 * LAMPORTS_PER_SOL] {
+                assert!(curve.buy(&start, sol_in).unwrap().fill_price >= spot, "{:?}", curve);
+            }
+            for tokens_in in [TOKEN, 1_000 * TOKEN, 100_000 * TOKEN] {
+                assert!(curve.sell(&start, tokens_in).unwrap().fill_price <= spot, "{:?}", curve);
+            }
+        }
+    }
+
+    #[test]
+    fn every_curve_loses_at_least_the_fees_on_a_round_trip() {
+        for curve in [linear(), exponential(), constant_product(), stepped()] {
+            for (reserve, supply) in [(0, 0), (50 * LAMPORTS_PER_SOL, 200_000 * TOKEN)] {
+                let start = state(lamports(10_000), reserve, supply);
+                let start = CurveState { price: curve.quote(&start).unwrap(), ..start };
+
+                for sol_in in [MIN_BUY_AMOUNT, LAMPORTS_PER_SOL, 10 * LAMPORTS_PER_SOL] {
+                    let bought = curve.buy(&start, sol_in).unwrap();
+                    let current = after_buy(&start, sol_in, &bought);
+
+                    // Before fees the curve alone never pays back more than it took
+                    let fee_free = curve.sell(&current, bought.amount_out).unwrap();
+                    assert!(fee_free.amount_out <= sol_in, "{:?} {} -> {}", curve, sol_in, fee_free.amount_out);
+                    assert!(fee_free.fill_price <= bought.fill_price, "{:?}", curve);
+
+                    // So the buy and sell fees are lost on top
+                    let received = math::split_buy(bought.amount_out).unwrap().tokens_to_buyer;
+                    let converted = math::split_sell(received, 0).unwrap().tokens_to_convert;
+                    let sold = curve.sell(&current, converted).unwrap();
+                    assert!(sold.amount_out < fee_free.amount_out, "{:?}", curve);
+                }
+            }
+        }
+    }
+
+    /// Buy `total` lamports in `chunks` equal buys, then sell everything in one trade
+    fn chunked_round_trip(curve: &CurveKind, start: CurveState, total: u64, chunks: u64) -> u64 {
+        let mut current = start;
+        let mut bought = 0;
+        for _ in 0..chunks {
+            let fill = curve.buy(&current, total / chunks).unwrap();
+            bought += fill.amount_out;
+            current = after_buy(&current, total / chunks, &fill);
+        }
+        curve.sell(&current, bought).unwrap().amount_out
+    }
+
+    #[test]
+    fn every_curve_loses_on_chunked_buys_sold_at_once() {
+        let steep = [
+            CurveKind::Linear { increment: 100 * PRICE_SCALE, decrement: 100 * PRICE_SCALE },
+            CurveKind::Exponential { growth_bps: BASIS_POINTS },
+            CurveKind::ConstantProduct { virtual_sol: LAMPORTS_PER_SOL, virtual_tokens: 1_000_000 * TOKEN },
+            CurveKind::Stepped { step_size: 10_000 * TOKEN, step_increment: lamports(1_000) },
+        ];
+        for curve in [linear(), exponential(), constant_product(), stepped()].iter().chain(steep.iter()) {
+            assert!(curve.validate().is_ok());
+            for (reserve, supply) in [(0, 0), (50 * LAMPORTS_PER_SOL, 200_000 * TOKEN)] {
+                let start = state(lamports(10_000), reserve, supply);
+                let start = CurveState { price: curve.quote(&start).unwrap(), ..start };
+                for chunks in [1, 2, 10, 50] {
+                    let total = 10 * LAMPORTS_PER_SOL;
+                    let returned = chunked_round_trip(curve, start, total, chunks);
+                    assert!(returned <= total, "{:?} in {} chunks: {} -> {}", curve, chunks, total, returned);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn linear_loses_on_chunked_buys_sold_at_once() {
+        let start = state(lamports(10_000), 0, 0);
+        let total = 10 * LAMPORTS_PER_SOL;
+        for chunks in [1, 2, 10, 50] {
+            let returned = chunked_round_trip(&linear(), start, total, chunks);
+            assert!(returned < total, "{} chunks: {} -> {}", chunks, total, returned);
+        }
+
+        // A sell impact smaller than the buy impact would let chunked buys profit
+        let lopsided = CurveKind::Linear { increment: 100 * PRICE_SCALE, decrement: 0 };
+        assert!(chunked_round_trip(&lopsided, start, total, 10) > total);
+        assert!(lopsided.validate().is_err());
+    }
+
+    #[test]
+    fn linear_and_exponential_sells_never_push_price_below_min_price() {
+        for curve in [linear(), exponential()] {
+            let floor = state(math::min_price(), 0, 0);
+            assert_eq!(curve.sell(&floor, 1_000_000 * TOKEN).unwrap().price_after, math::min_price());
+            let above = state(lamports(crate::MIN_PRICE + 1), 0, 0);
+            assert_eq!(curve.sell(&above, 100_000_000 * TOKEN).unwrap().price_after, math::min_price());
+        }
+    }
+
+    #[test]
+    fn exponential_buy_and_matching_sell_restore_the_price() {
+        let curve = ExponentialCurve { growth_bps: 1_000 };
+        let start = state(lamports(10_000), 0, 0);
+        let after = curve.buy(&start, 10 * LAMPORTS_PER_SOL).unwrap().price_after;
+        // 10 SOL at 1% per SOL compounds to +10%
+        assert_eq!(after, lamports(11_000));
+
+        // Selling tokens worth exactly 10 SOL at the new price undoes the move
+        let tokens = math::tokens_for_sol(10 * LAMPORTS_PER_SOL, after, DECIMALS, Rounding::Down).unwrap();
+        let fill = curve.sell(&state(after, 0, 0), tokens).unwrap();
+        assert!(fill.price_after.abs_diff(start.price) <= start.price / 1_000_000_000);
+    }
+
+    #[test]
+    fn exponential_growth_is_relative_to_price() {
+        let curve = exponential();
+        let low = curve.buy(&state(lamports(1_000), 0, 0), LAMPORTS_PER_SOL).unwrap();
+        let high = curve.buy(&state(lamports(100_000), 0, 0), LAMPORTS_PER_SOL).unwrap();
+        assert_eq!(low.price_after, lamports(1_010));
+        assert_eq!(high.price_after, lamports(101_000));
+    }
+
+    #[test]
+    fn constant_product_never_shrinks_k() {
+        let curve = ConstantProductCurve { virtual_sol: 30 * LAMPORTS_PER_SOL, virtual_tokens: 1_000_000_000 * TOKEN };
+        let k = |s: &CurveState| {
+            let (sol, tokens) = curve.reserves(s).unwrap();
+            sol * tokens
+        };
+
+        let mut current = state(0, 0, 0);
+        let k0 = k(&current);
+        for sol_in in [MIN_BUY_AMOUNT, 3 * LAMPORTS_PER_SOL, MAX_BUY_AMOUNT] {
+            let fill = curve.buy(&current, sol_in).unwrap();
+            current = after_buy(&current, sol_in, &fill);
+            assert!(k(&current) >= k0);
+        }
+        for tokens_in in [TOKEN, 1_000_000 * TOKEN, current.supply / 2] {
+            let fill = curve.sell(&current, tokens_in).unwrap();
+            current = after_sell(&current, tokens_in, &fill);
+            assert!(k(&current) >= k0);
+        }
+    }
+
+    #[test]
+    fn constant_product_price_matches_reserves() {
+        let curve = constant_product();
+        let start = state(0, 0, 0);
+        // 30 SOL against 1B tokens: 30 lamports per token
+        assert_eq!(curve.quote(&start).unwrap(), lamports(30));
+
+        let fill = curve.buy(&start, 30 * LAMPORTS_PER_SOL).unwrap();
+        // Doubling the SOL side halves the token side: 4x the price
+        assert_eq!(fill.amount_out, 500_000_000 * TOKEN);
+        assert_eq!(fill.price_after, lamports(120));
+        assert_eq!(curve.quote(&after_buy(&start, 30 * LAMPORTS_PER_SOL, &fill)).unwrap(), fill.price_after);
+    }
+
+    #[test]
+    fn constant_product_sell_of_everything_returns_at_most_the_reserve() {
+        let curve = constant_product();
+        let start = state(0, 0, 0);
+        let fill = curve.buy(&start, MAX_BUY_AMOUNT).unwrap();
+        let bought = after_buy(&start, MAX_BUY_AMOUNT, &fill);
+        let sold = curve.sell(&bought, fill.amount_out).unwrap();
+        assert!(sold.amount_out <= MAX_BUY_AMOUNT);
+        assert!(MAX_BUY_AMOUNT - sold.amount_out <= 1);
+    }
+
+    #[test]
+    fn constant_product_rejects_buys_once_exhausted() {
+        let curve = constant_product();
+        assert!(curve.buy(&state(0, 0, 1_000_000_000 * TOKEN), LAMPORTS_PER_SOL).is_err());
+    }
+
+    #[test]
+    fn stepped_price_is_flat_within_a_step() {
+        let curve = SteppedCurve { step_size: 100_000 * TOKEN, step_increment: lamports(100) };
+        let start = state(lamports(10_000), 0, 0);
+        // 0.5 SOL buys 50,000 tokens at 10,000 lamports and stays in the first step
+        let fill = curve.buy(&start, LAMPORTS_PER_SOL / 2).unwrap();
+        assert_eq!(fill.amount_out, 50_000 * TOKEN);
+        assert_eq!(fill.price_after, start.price);
+    }
+
+    #[test]
+    fn stepped_buy_crosses_steps_at_each_step_price() {
+        let curve = SteppedCurve { step_size: 100_000 * TOKEN, step_increment: lamports(100) };
+        let start = state(lamports(10_000), 0, 0);
+        // First step costs 1 SOL, the second 1.01 SOL
+        let fill = curve.buy(&start, 2_010_000_000).unwrap();
+        assert_eq!(fill.amount_out, 200_000 * TOKEN);
+        assert_eq!(fill.price_after, lamports(10_200));
+
+        // Selling both steps back walks the price down and pays the same SOL
+        let bought = after_buy(&start, 2_010_000_000, &fill);
+        let sold = curve.sell(&bought, fill.amount_out).unwrap();
+        assert_eq!(sold.amount_out, 2_010_000_000);
+        assert_eq!(sold.price_after, start.price);
+    }
+
+    #[test]
+    fn stepped_sell_past_issued_supply_stays_at_base_price() {
+        let curve = SteppedCurve { step_size: 100_000 * TOKEN, step_increment: lamports(100) };
+        let current = state(lamports(10_000), LAMPORTS_PER_SOL, 50_000 * TOKEN);
+        let fill = curve.sell(&current, 80_000 * TOKEN).unwrap();
+        assert_eq!(fill.amount_out, 800_000_000);
+        assert_eq!(fill.price_after, current.price);
+    }
+
+    #[test]
+    fn stepped_trades_are_limited_to_max_curve_steps() {
+        let curve = SteppedCurve { step_size: TOKEN, step_increment: 1 };
+        let start = state(lamports(10_000), 0, 0);
+        let cost = math::sol_for_tokens(TOKEN, start.price, DECIMALS, Rounding::Up).unwrap();
+        assert!(curve.buy(&start, cost * (MAX_CURVE_STEPS as u64 - 1)).is_ok());
+        assert_eq!(
+            curve.buy(&start, cost * (MAX_CURVE_STEPS as u64 + 1)).unwrap_err(),
+            error!(ErrorCode::CurveStepLimit)
+        );
+    }
+}
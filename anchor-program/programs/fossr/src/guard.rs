@@ -0,0 +1,42 @@
+//! Trade guards against bundled and sandwiched trades.
+//!
+//! A buy and a sell from the same transaction are rejected by reading the
+//! Instructions sysvar. Trades must be top-level instructions: a CPI would hide
+//! the wrapped trades from the sysvar, which only lists the outer instructions.
+
+use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{get_stack_height, TRANSACTION_LEVEL_STACK_HEIGHT};
+use anchor_lang::solana_program::sysvar::instructions::load_instruction_at_checked;
+use anchor_lang::Discriminator;
+
+use crate::{instruction, ErrorCode};
+
+/// Instructions that buy from the curve
+pub const BUY_INSTRUCTIONS: &[[u8; 8]] = &[
+    instruction::BuyTokens::DISCRIMINATOR,
+    instruction::BuyExactTokens::DISCRIMINATOR,
+    instruction::BuybackAndBurn::DISCRIMINATOR,
+    instruction::ExecuteLimitBuy::DISCRIMINATOR,
+    instruction::ExecuteDca::DISCRIMINATOR,
+];
+/// Instructions that sell to the curve
+pub const SELL_INSTRUCTIONS: &[[u8; 8]] = &[
+    instruction::SellTokens::DISCRIMINATOR,
+    instruction::ExecuteSellOrder::DISCRIMINATOR,
+];
+
+/// Fail unless the current trade is top-level and no instruction in the
+/// transaction invokes this program with one of the `opposite` discriminators
+pub fn reject_opposite_trades(instructions: &AccountInfo, opposite: &[[u8; 8]]) -> Result<()> {
+    require!(get_stack_height() == TRANSACTION_LEVEL_STACK_HEIGHT, ErrorCode::TradeViaCpi);
+
+    let mut index = 0;
+    while let Ok(ix) = load_instruction_at_checked(index, instructions) {
+        let is_opposite = ix.program_id == crate::ID
+            && ix.data.len() >= 8
+            && opposite.iter().any(|discriminator| ix.data[..8] == discriminator[..]);
+        require!(!is_opposite, ErrorCode::BuyAndSellInSameTransaction);
+        index += 1;
+    }
+    Ok(())
+}
@@ -1,7 +1,27 @@
    use anchor_lang::prelude::*;
+use anchor_lang::solana_program::program_option::COption;
 use anchor_spl::token::{self, Mint, Token, TokenAccount};
 use anchor_spl::associated_token::AssociatedToken;
 
+pub mod breaker;
+pub mod buyback;
+pub mod curve;
+pub mod guard;
+pub mod limits;
+pub mod math;
+pub mod oracle;
+pub mod staking;
+pub mod supply;
+pub mod vesting;
+
+use breaker::CircuitBreaker;
+use buyback::Buyback;
+use curve::{BondingCurve, CurveKind, CurveState};
+use limits::{BuyLimits, SellLimits, WindowUsage};
+use oracle::ObservationRing;
+use staking::RewardAccumulator;
+use supply::{SupplyLedger, SupplyReport};
+
 // Must match the deployed program id (Anchor enforces this at runtime)
 declare_id!("5WCXWwsaw8WRxMzxqBiAQ5ByHWY9ruV9egijtgC493SP");
 
@@ -10,12 +30,17 @@ pub const BURN_FEE_BPS: u16 = 69;             // 0.069% burn on buys
 pub const DEV_FEE_BPS: u16 = 31;              // 0.031% dev fee
 pub const RAFFLE_FEE_BPS: u16 = 621;          // 0.621% added to airdrop pot on buys
 pub const SELL_BURN_FEE_BPS: u16 = 10;        // 0.1% burn on sells
+pub const EARLY_SELL_TAX_BPS: u16 = 5_000;    // 5% into the airdrop pot when selling right after buying
+pub const EARLY_SELL_TAX_PERIOD: i64 = 24 * 60 * 60; // Early-sell tax decays to zero over a day of holding
 pub const BASIS_POINTS: u32 = 100_000;        // 100% = 100,000 bps
+pub const DEFAULT_REFERRAL_SHARE_BPS: u32 = 10_000; // 10% of the raffle fee goes to the buyer's referrer
+pub const CRANK_REWARD: u64 = 100_000;        // 0.0001 SOL to whoever executes an order
 
 /// Bonding curve constants
-pub const PRICE_INCREMENT: u64 = 1;
-pub const PRICE_DECREMENT: u64 = 1;
-pub const MIN_PRICE: u64 = 100;               // Minimum price floor
+pub const PRICE_INCREMENT: u64 = 1;           // Default linear curve: lamports per token added per SOL bought
+pub const PRICE_DECREMENT: u64 = 1;           // Default linear curve: lamports per token removed per SOL sold
+pub const MIN_PRICE: u64 = 100;               // Minimum price floor (lamports per token)
+pub const PRICE_SCALE: u128 = 1_000_000_000;  // current_price = lamports per token * PRICE_SCALE
 
 /// Lock tier durations (in seconds)
 pub const TIER1_MIN: i64 = 5 * 60;            // 5 minutes
@@ -24,8 +49,8 @@ pub const TIER2_MIN: i64 = 4 * 60;            // 4 minutes
 pub const TIER2_MAX: i64 = 4 * 60 * 60;       // 4 hours
 pub const TIER3_MIN: i64 = 3 * 60;            // 3 minutes
 pub const TIER3_MAX: i64 = 3 * 60 * 60;       // 3 hours
-pub const TIER4_MIN: i64 = 1 * 60;            // 1 minute
-pub const TIER4_MAX: i64 = 1 * 60 * 60;       // 1 hour
+pub const TIER4_MIN: i64 = 60;                // 1 minute
+pub const TIER4_MAX: i64 = 60 * 60;           // 1 hour
 
 /// SOL amount thresholds (in lamports)
 pub const TIER1_THRESHOLD: u64 = 100_000_000;       // 0.1 SOL
@@ -35,26 +60,46 @@ pub const TIER3_THRESHOLD: u64 = 1_000_000_000;     // 1.0 SOL
 /// Min/max buy amounts
 pub const MIN_BUY_AMOUNT: u64 = 10_000_000;         // 0.01 SOL
 pub const MAX_BUY_AMOUNT: u64 = 100_000_000_000;    // 100 SOL
-pub const MIN_SELL_AMOUNT: u64 = 1_000_000_000;     // 1 token
+pub const MIN_SELL_AMOUNT: u64 = 1;                 // 1 whole token, scaled by mint decimals
+
+/// Slots a wallet must wait after its last buy before it can sell
+pub const MIN_BUY_SELL_SLOT_GAP: u64 = 3;
+
+/// Most recipients a fee split can list
+pub const MAX_FEE_RECIPIENTS: usize = 8;
+
+/// Largest mint decimals the fixed-point math supports
+pub const MAX_TOKEN_DECIMALS: u8 = 18;
 
 #[program]
 pub mod fossr {
     use super::*;
 
-    /// Initialize the FOSSR program state (simplified)
-    pub fn initialize(ctx: Context<Initialize>, initial_price: u64, airdrop_amount: u64) -> Result<()> {
+    /// Initialize the FOSSR program state (simplified).
+    ///
+    /// Buys, sells and airdrops update the staking pool, trade stats and price
+    /// oracle, so `initialize_staking`, `initialize_stats` and
+    /// `initialize_oracle` must all run before the first trade.
+    pub fn initialize(ctx: Context<Initialize>, initial_price: u64, airdrop_amount: u64, curve: CurveKind) -> Result<()> {
+        require!(ctx.accounts.token_mint.decimals <= MAX_TOKEN_DECIMALS, ErrorCode::InvalidMintDecimals);
+        
         let clock = Clock::get()?;
-        let state = &mut ctx.accounts.program_state;
-        state.authority = ctx.accounts.authority.key();
-        state.token_mint = ctx.accounts.token_mint.key();
-        state.current_price = initial_price;
-        state.total_burned = 0;
-        state.total_buys = 0;
-        state.next_airdrop_time = calculate_next_airdrop_time(clock.unix_timestamp);
-        state.airdrop_amount = airdrop_amount;
-        state.last_airdrop_cycle = clock.unix_timestamp;
-        state.airdrop_executed = false;
-        state.bump = ctx.bumps.program_state;
+        let mut state = ProgramState::launch(
+            ctx.accounts.authority.key(),
+            ctx.accounts.token_mint.key(),
+            ctx.accounts.token_mint.supply,
+            ctx.bumps.program_state,
+            airdrop_amount,
+            curve,
+            clock.unix_timestamp,
+        )?;
+
+        // Curves that derive their price from reserves ignore `initial_price`
+        state.current_price = math::price_from_lamports(initial_price);
+        state.current_price = curve.quote(&state.curve_state(ctx.accounts.token_mint.decimals))?;
+        state.reset_circuit_breaker(clock.unix_timestamp);
+        let price = state.current_price;
+        ctx.accounts.program_state.set_inner(state);
 
         // Ensure the SOL vault PDA exists so buys can transfer SOL into it.
         // If it doesn't exist yet, create it as a 0-byte account owned by this program.
@@ -80,15 +125,73 @@ pub mod fossr {
             )?;
         }
 
-        msg!("FOSSR initialized | Price: {} | Airdrop every 5 minutes", initial_price);
+        msg!("FOSSR initialized | Price: {} | Curve: {:?} | Airdrop every 5 minutes", price, curve);
+        Ok(())
+    }
+
+    /// Rewrite a program state created by the original program (before curves,
+    /// limits and fee accounting existed) in the current layout, keeping its
+    /// authority, mint, totals and airdrop schedule (authority only).
+    ///
+    /// Everything above rent in the vault becomes the curve's SOL reserve and
+    /// the whole mint supply counts as issued by the curve, since the original
+    /// program did not track either. Settings start at `initialize`'s defaults,
+    /// and staking, stats and the oracle still have to be initialized before
+    /// the next trade.
+    pub fn migrate_program_state(ctx: Context<MigrateProgramState>, curve: CurveKind) -> Result<()> {
+        let clock = Clock::get()?;
+        let info = ctx.accounts.program_state.to_account_info();
+        let legacy = LegacyProgramState::read(&info.try_borrow_data()?)?;
+        require_keys_eq!(legacy.authority, ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+        require_keys_eq!(legacy.token_mint, ctx.accounts.token_mint.key(), ErrorCode::InvalidMint);
+        
+        let rent = Rent::get()?;
+        let vault = &ctx.accounts.program_vault;
+        let mut state = ProgramState::launch(
+            legacy.authority,
+            legacy.token_mint,
+            ctx.accounts.token_mint.supply,
+            legacy.bump,
+            legacy.airdrop_amount,
+            curve,
+            clock.unix_timestamp,
+        )?;
+        state.total_burned = legacy.total_burned;
+        state.total_buys = legacy.total_buys;
+        state.next_airdrop_time = legacy.next_airdrop_time;
+        state.last_airdrop_cycle = legacy.last_airdrop_cycle;
+        state.airdrop_executed = legacy.airdrop_executed;
+        state.sol_reserve = vault.lamports().saturating_sub(rent.minimum_balance(vault.data_len()));
+        state.curve_supply = ctx.accounts.token_mint.supply;
+        state.current_price = math::price_from_lamports(legacy.current_price);
+        state.current_price = curve.quote(&state.curve_state(ctx.accounts.token_mint.decimals))?;
+        state.reset_circuit_breaker(clock.unix_timestamp);
+        
+        // Grow the account to the current layout, topping up its rent
+        let space = 8 + ProgramState::INIT_SPACE;
+        let shortfall = rent.minimum_balance(space).saturating_sub(info.lamports());
+        if shortfall > 0 {
+            anchor_lang::system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    anchor_lang::system_program::Transfer {
+                        from: ctx.accounts.authority.to_account_info(),
+                        to: info.clone(),
+                    },
+                ),
+                shortfall,
+            )?;
+        }
+        info.realloc(space, true)?;
+        state.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+        
+        msg!("Program state migrated | Price: {} | Reserve: {} | Curve: {:?}", state.current_price, state.sol_reserve, curve);
         Ok(())
     }
 
     /// One-time helper: create the SOL vault PDA if it doesn't exist yet.
     /// This fixes "Attempt to debit an account but found no record of a prior credit" on buys.
     pub fn initialize_vault(ctx: Context<InitializeVault>) -> Result<()> {
-        require_keys_eq!(ctx.accounts.authority.key(), ctx.accounts.program_state.authority, ErrorCode::Unauthorized);
-
         if ctx.accounts.program_vault.lamports() > 0 {
             msg!("Vault already initialized");
             return Ok(());
@@ -120,179 +223,485 @@ pub mod fossr {
 
     /// Buy tokens with tiered protective locks
     pub fn buy_tokens(ctx: Context<BuyTokens>, sol_amount: u64, _timestamp: i64) -> Result<()> {
-        let clock = Clock::get()?;
-        
-        // Get account info before mutable borrow
-        let program_state_info = ctx.accounts.program_state.to_account_info();
-        let state_bump = ctx.accounts.program_state.bump;
-        
-        let state = &mut ctx.accounts.program_state;
-        
-        require!(sol_amount >= MIN_BUY_AMOUNT, ErrorCode::BuyAmountTooSmall);
-        require!(sol_amount <= MAX_BUY_AMOUNT, ErrorCode::BuyAmountTooLarge);
-        require!(state.current_price > 0, ErrorCode::InvalidPrice);
+        guard::reject_opposite_trades(&ctx.accounts.instructions, guard::SELL_INSTRUCTIONS)?;
+        buy_with(ctx.accounts, &ctx.bumps, sol_amount)?;
+        Ok(())
+    }
+
+    /// Buy at least `token_amount` atomic units after fees, spending within a
+    /// couple of atoms' worth of the least SOL that delivers them, and fail if
+    /// that exceeds `max_sol_in`
+    pub fn buy_exact_tokens(ctx: Context<BuyTokens>, token_amount: u64, _timestamp: i64, max_sol_in: u64) -> Result<()> {
+        guard::reject_opposite_trades(&ctx.accounts.instructions, guard::SELL_INSTRUCTIONS)?;
+        require!(token_amount > 0, ErrorCode::BuyAmountTooSmall);
         
-        // Calculate tokens based on bonding curve price
-        let tokens_before_fees = sol_amount
-            .checked_mul(1_000_000_000)
-            .ok_or(ErrorCode::ArithmeticOverflow)?
-            .checked_div(state.current_price)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        let state = &ctx.accounts.program_state;
+        let curve_state = state.curve_state(ctx.accounts.token_mint.decimals);
+        let sol_amount = sol_for_exact_tokens(&state.curve, &curve_state, token_amount)?;
+        require!(sol_amount <= max_sol_in, ErrorCode::SlippageExceeded);
         
-        // Calculate burn fee (0.069%)
-        let burn_amount = tokens_before_fees
-            .checked_mul(BURN_FEE_BPS as u64)
-            .ok_or(ErrorCode::ArithmeticOverflow)?
-            .checked_div(BASIS_POINTS as u64)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        let tokens_to_buyer = buy_with(ctx.accounts, &ctx.bumps, sol_amount)?;
+        require!(tokens_to_buyer >= token_amount, ErrorCode::SlippageExceeded);
+        Ok(())
+    }
 
-        // Calculate raffle fee (0.621%) which accumulates into the global airdrop pot
-        let raffle_fee_amount = tokens_before_fees
-            .checked_mul(RAFFLE_FEE_BPS as u64)
-            .ok_or(ErrorCode::ArithmeticOverflow)?
-            .checked_div(BASIS_POINTS as u64)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+    /// Let `payer` buy tokens on the owner's behalf. Gifted buys count toward
+    /// the owner's buy caps and restart their sell slot gap and hold timer,
+    /// so only payers the owner trusts may make them.
+    pub fn allow_gifts(ctx: Context<AllowGifts>, payer: Pubkey) -> Result<()> {
+        let allowance = &mut ctx.accounts.gift_allowance;
         
-        let tokens_to_buyer = tokens_before_fees
-            .checked_sub(burn_amount)
-            .ok_or(ErrorCode::ArithmeticOverflow)?
-            .checked_sub(raffle_fee_amount)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        allowance.beneficiary = ctx.accounts.beneficiary.key();
+        allowance.payer = payer;
+        allowance.bump = ctx.bumps.gift_allowance;
         
-        // Calculate lock duration
-        let slot_hashes_data = ctx.accounts.slot_hashes.data.borrow();
-        let lock_duration = calculate_lock_duration(sol_amount, clock.unix_timestamp, clock.slot, &slot_hashes_data)?;
-        let unlock_time = clock.unix_timestamp + lock_duration;
+        msg!("Gifts allowed from {}", payer);
+        Ok(())
+    }
+
+    /// Stop a payer from buying on the owner's behalf and refund the allowance's rent
+    pub fn revoke_gift_allowance(ctx: Context<RevokeGiftAllowance>) -> Result<()> {
+        msg!("Gifts no longer allowed from {}", ctx.accounts.gift_allowance.payer);
+        Ok(())
+    }
+
+    /// Escrow SOL for a buy that executes once the price is at or below
+    /// `target_price`, until `expiry`. The escrow also covers the crank reward
+    /// and the rent of the purchase order created when it fills.
+    pub fn place_limit_buy(
+        ctx: Context<PlaceLimitBuy>,
+        id: u64,
+        sol_amount: u64,
+        target_price: u128,
+        expiry: i64,
+    ) -> Result<()> {
+        require!(sol_amount >= MIN_BUY_AMOUNT, ErrorCode::BuyAmountTooSmall);
+        require!(sol_amount <= MAX_BUY_AMOUNT, ErrorCode::BuyAmountTooLarge);
+        require!(target_price > 0 && expiry > Clock::get()?.unix_timestamp, ErrorCode::InvalidOrder);
         
-        // Transfer SOL to program vault
+        let purchase_order_rent = Rent::get()?.minimum_balance(8 + PurchaseOrder::INIT_SPACE);
+        let crank_fee = CRANK_REWARD + purchase_order_rent;
         anchor_lang::system_program::transfer(
             CpiContext::new(
                 ctx.accounts.system_program.to_account_info(),
                 anchor_lang::system_program::Transfer {
-                    from: ctx.accounts.buyer.to_account_info(),
-                    to: ctx.accounts.program_vault.to_account_info(),
+                    from: ctx.accounts.owner.to_account_info(),
+                    to: ctx.accounts.limit_order.to_account_info(),
                 },
             ),
-            sol_amount,
+            sol_amount.checked_add(crank_fee).ok_or(ErrorCode::ArithmeticOverflow)?,
         )?;
         
-        // Mint tokens to buyer's ATA (not locked for MVP simplicity)
-        let bump = &[state_bump];
-        let seeds: &[&[u8]] = &[b"program-state", bump];
-        let signer = &[seeds];
+        // The fill can't charge the owner, so their profile must exist already
+        let profile = &mut ctx.accounts.owner_profile;
+        profile.wallet = ctx.accounts.owner.key();
+        profile.bump = ctx.bumps.owner_profile;
         
-        token::mint_to(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                token::MintTo {
-                    mint: ctx.accounts.token_mint.to_account_info(),
-                    to: ctx.accounts.buyer_token_account.to_account_info(),
-                    authority: program_state_info.clone(),
-                },
-                signer,
-            ),
-            tokens_to_buyer,
-        )?;
+        let order = &mut ctx.accounts.limit_order;
+        order.owner = ctx.accounts.owner.key();
+        order.id = id;
+        order.sol_amount = sol_amount;
+        order.target_price = target_price;
+        order.expiry = expiry;
+        order.crank_fee = crank_fee;
+        order.bump = ctx.bumps.limit_order;
         
-        // Update state (burn is implicit - we don't mint burn_amount)
-        state.total_burned = state.total_burned.checked_add(burn_amount).ok_or(ErrorCode::ArithmeticOverflow)?;
-        state.total_buys = state.total_buys.checked_add(1).ok_or(ErrorCode::ArithmeticOverflow)?;
-        // Global airdrop pot increases with each buy
-        state.airdrop_amount = state.airdrop_amount
-            .checked_add(raffle_fee_amount)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        msg!("Limit buy placed: {} lamports at or below {} until {}", sol_amount, target_price, expiry);
+        Ok(())
+    }
+
+    /// Close a limit buy and return its escrow to the owner
+    pub fn cancel_limit_buy(ctx: Context<CancelLimitBuy>) -> Result<()> {
+        msg!("Limit buy cancelled: {} lamports returned", ctx.accounts.limit_order.sol_amount);
+        Ok(())
+    }
+
+    /// Fill a limit buy through the normal buy path once the price has reached
+    /// its target (anyone can call). The caller pays the purchase order's rent
+    /// and is refunded it from the escrow along with the crank reward.
+    pub fn execute_limit_buy(ctx: Context<ExecuteLimitBuy>, _timestamp: i64) -> Result<()> {
+        let order = &ctx.accounts.limit_order;
+        require!(Clock::get()?.unix_timestamp < order.expiry, ErrorCode::OrderExpired);
+        require!(ctx.accounts.program_state.current_price <= order.target_price, ErrorCode::LimitPriceNotReached);
+        guard::reject_opposite_trades(&ctx.accounts.instructions, guard::SELL_INSTRUCTIONS)?;
         
-        // Update bonding curve price
-        let price_increase = sol_amount
-            .checked_div(1_000_000_000)
-            .ok_or(ErrorCode::ArithmeticOverflow)?
-            .max(1);
-        state.current_price = state.current_price.checked_add(price_increase).ok_or(ErrorCode::ArithmeticOverflow)?;
+        let (sol_amount, crank_fee) = (order.sol_amount, order.crank_fee);
+        let escrow = ctx.accounts.limit_order.to_account_info();
+        move_lamports(&escrow, &ctx.accounts.program_vault, sol_amount)?;
+        move_lamports(&escrow, &ctx.accounts.cranker.to_account_info(), crank_fee)?;
         
-        // Create purchase order for tracking
-        let order = &mut ctx.accounts.purchase_order;
-        order.buyer = ctx.accounts.buyer.key();
-        order.sol_amount = sol_amount;
-        order.token_amount = tokens_to_buyer;
-        order.timestamp = clock.unix_timestamp;
-        order.unlock_time = unlock_time;
-        order.bump = ctx.bumps.purchase_order;
+        let owner = ctx.accounts.owner.key();
+        let token_amount = execute_buy(
+            BuyAccounts {
+                program_state: &mut ctx.accounts.program_state,
+                token_mint: &ctx.accounts.token_mint,
+                buyer_token_account: &ctx.accounts.owner_token_account,
+                buyer_profile: &mut ctx.accounts.owner_profile,
+                buyer_profile_bump: ctx.bumps.owner_profile,
+                purchase_order: &mut ctx.accounts.purchase_order,
+                purchase_order_bump: ctx.bumps.purchase_order,
+                stake_pool: &mut ctx.accounts.stake_pool,
+                stats: &mut ctx.accounts.stats,
+                price_oracle: &mut ctx.accounts.price_oracle,
+                slot_hashes: &ctx.accounts.slot_hashes,
+                token_program: &ctx.accounts.token_program,
+                referrer_profile: None,
+                referrer_rewards: None,
+                referrer_rewards_bump: None,
+            },
+            owner,
+            sol_amount,
+        )?;
         
-        msg!(
-            "Buy: {} tokens for {} lamports | Raffle pot +{} | Lock until: {}",
-            tokens_to_buyer,
+        emit!(LimitBuyExecuted {
+            order: ctx.accounts.limit_order.key(),
+            owner,
             sol_amount,
-            raffle_fee_amount,
-            unlock_time
-        );
+            token_amount,
+            cranker: ctx.accounts.cranker.key(),
+        });
         Ok(())
     }
 
     /// Sell tokens back to bonding curve
     pub fn sell_tokens(ctx: Context<SellTokens>, token_amount: u64) -> Result<()> {
-        let state = &mut ctx.accounts.program_state;
+        guard::reject_opposite_trades(&ctx.accounts.instructions, guard::BUY_INSTRUCTIONS)?;
+        
+        let seller = ctx.accounts.seller.key();
+        let sol_amount = execute_sell(
+            SellAccounts {
+                program_state: &mut ctx.accounts.program_state,
+                token_mint: &ctx.accounts.token_mint,
+                source: &ctx.accounts.seller_token_account,
+                source_authority: ctx.accounts.seller.to_account_info(),
+                source_signer: &[],
+                seller_profile: &mut ctx.accounts.seller_profile,
+                seller_profile_bump: ctx.bumps.seller_profile,
+                stake_pool: &mut ctx.accounts.stake_pool,
+                stats: &mut ctx.accounts.stats,
+                price_oracle: &mut ctx.accounts.price_oracle,
+                program_vault: &ctx.accounts.program_vault,
+                token_program: &ctx.accounts.token_program,
+            },
+            seller,
+            token_amount,
+        )?;
         
-        require!(token_amount >= MIN_SELL_AMOUNT, ErrorCode::SellAmountTooSmall);
-        require!(ctx.accounts.seller_token_account.amount >= token_amount, ErrorCode::InsufficientBalance);
-        require!(state.current_price > 0, ErrorCode::InvalidPrice);
+        // Transfer SOL back
+        move_lamports(&ctx.accounts.program_vault, &ctx.accounts.seller.to_account_info(), sol_amount)?;
+        Ok(())
+    }
+
+    /// Pre-fund a schedule that buys `amount_per_interval` lamports' worth
+    /// every `interval` seconds, `intervals` times. The deposit also covers
+    /// each instalment's crank reward and purchase order rent.
+    pub fn create_dca(
+        ctx: Context<CreateDca>,
+        id: u64,
+        amount_per_interval: u64,
+        interval: i64,
+        intervals: u32,
+    ) -> Result<()> {
+        require!(amount_per_interval >= MIN_BUY_AMOUNT, ErrorCode::BuyAmountTooSmall);
+        require!(amount_per_interval <= MAX_BUY_AMOUNT, ErrorCode::BuyAmountTooLarge);
+        require!(interval > 0 && intervals > 0, ErrorCode::InvalidOrder);
         
-        // Calculate burn fee
-        let sell_burn = token_amount
-            .checked_mul(SELL_BURN_FEE_BPS as u64)
-            .ok_or(ErrorCode::ArithmeticOverflow)?
-            .checked_div(BASIS_POINTS as u64)
+        let crank_fee = CRANK_REWARD + Rent::get()?.minimum_balance(8 + PurchaseOrder::INIT_SPACE);
+        let deposit = amount_per_interval
+            .checked_add(crank_fee)
+            .and_then(|instalment| instalment.checked_mul(intervals as u64))
             .ok_or(ErrorCode::ArithmeticOverflow)?;
+        anchor_lang::system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                anchor_lang::system_program::Transfer {
+                    from: ctx.accounts.owner.to_account_info(),
+                    to: ctx.accounts.dca_schedule.to_account_info(),
+                },
+            ),
+            deposit,
+        )?;
         
-        let tokens_to_convert = token_amount.checked_sub(sell_burn).ok_or(ErrorCode::ArithmeticOverflow)?;
+        // The instalments can't charge the owner, so their profile must exist already
+        let profile = &mut ctx.accounts.owner_profile;
+        profile.wallet = ctx.accounts.owner.key();
+        profile.bump = ctx.bumps.owner_profile;
         
-        // Calculate SOL to return
-        let sol_amount = tokens_to_convert
-            .checked_mul(state.current_price)
-            .ok_or(ErrorCode::ArithmeticOverflow)?
-            .checked_div(1_000_000_000)
-            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        let schedule = &mut ctx.accounts.dca_schedule;
+        schedule.owner = ctx.accounts.owner.key();
+        schedule.id = id;
+        schedule.amount_per_interval = amount_per_interval;
+        schedule.interval = interval;
+        schedule.intervals_remaining = intervals;
+        schedule.next_run = Clock::get()?.unix_timestamp;
+        schedule.crank_fee = crank_fee;
+        schedule.bump = ctx.bumps.dca_schedule;
+        
+        msg!("DCA created: {} lamports every {}s, {} times", amount_per_interval, interval, intervals);
+        Ok(())
+    }
+
+    /// Close a DCA schedule and return the unspent deposit to the owner
+    pub fn stop_dca(ctx: Context<StopDca>) -> Result<()> {
+        msg!("DCA stopped with {} instalments left", ctx.accounts.dca_schedule.intervals_remaining);
+        Ok(())
+    }
+
+    /// Buy the next due instalment of a DCA schedule through the normal buy
+    /// path (anyone can call). The caller pays the purchase order's rent and
+    /// is refunded it from the deposit along with the crank reward.
+    pub fn execute_dca(ctx: Context<ExecuteDca>, _timestamp: i64) -> Result<()> {
+        let now = Clock::get()?.unix_timestamp;
+        let schedule = &ctx.accounts.dca_schedule;
+        require!(now >= schedule.next_run, ErrorCode::DcaNotDue);
+        guard::reject_opposite_trades(&ctx.accounts.instructions, guard::SELL_INSTRUCTIONS)?;
         
-        require!(ctx.accounts.program_vault.lamports() >= sol_amount, ErrorCode::InsufficientVaultBalance);
+        let (sol_amount, crank_fee) = (schedule.amount_per_interval, schedule.crank_fee);
+        let deposit = ctx.accounts.dca_schedule.to_account_info();
+        move_lamports(&deposit, &ctx.accounts.program_vault, sol_amount)?;
+        move_lamports(&deposit, &ctx.accounts.cranker.to_account_info(), crank_fee)?;
+        
+        let owner = ctx.accounts.owner.key();
+        let token_amount = execute_buy(
+            BuyAccounts {
+                program_state: &mut ctx.accounts.program_state,
+                token_mint: &ctx.accounts.token_mint,
+                buyer_token_account: &ctx.accounts.owner_token_account,
+                buyer_profile: &mut ctx.accounts.owner_profile,
+                buyer_profile_bump: ctx.bumps.owner_profile,
+                purchase_order: &mut ctx.accounts.purchase_order,
+                purchase_order_bump: ctx.bumps.purchase_order,
+                stake_pool: &mut ctx.accounts.stake_pool,
+                stats: &mut ctx.accounts.stats,
+                price_oracle: &mut ctx.accounts.price_oracle,
+                slot_hashes: &ctx.accounts.slot_hashes,
+                token_program: &ctx.accounts.token_program,
+                referrer_profile: None,
+                referrer_rewards: None,
+                referrer_rewards_bump: None,
+            },
+            owner,
+            sol_amount,
+        )?;
+        
+        // Late cranks don't bunch up the remaining instalments
+        let schedule = &mut ctx.accounts.dca_schedule;
+        schedule.intervals_remaining -= 1;
+        schedule.next_run = now.checked_add(schedule.interval).ok_or(ErrorCode::ArithmeticOverflow)?;
+        let remaining = schedule.intervals_remaining;
+        
+        emit!(DcaInstalmentExecuted {
+            schedule: schedule.key(),
+            owner,
+            sol_amount,
+            token_amount,
+            intervals_remaining: remaining,
+        });
+        if remaining == 0 {
+            ctx.accounts.dca_schedule.close(ctx.accounts.owner.to_account_info())?;
+        }
+        Ok(())
+    }
+
+    /// Escrow tokens for a stop-loss or take-profit sell that executes once
+    /// the price crosses `trigger_price`, until `expiry`. The owner receives at
+    /// least `min_sol_out` lamports after the crank reward, or it doesn't fill.
+    pub fn place_sell_order(
+        ctx: Context<PlaceSellOrder>,
+        id: u64,
+        token_amount: u64,
+        trigger_price: u128,
+        direction: TriggerDirection,
+        min_sol_out: u64,
+        expiry: i64,
+    ) -> Result<()> {
+        let min_tokens = math::whole_tokens(MIN_SELL_AMOUNT, ctx.accounts.token_mint.decimals)?;
+        require!(token_amount >= min_tokens, ErrorCode::SellAmountTooSmall);
+        require!(trigger_price > 0 && expiry > Clock::get()?.unix_timestamp, ErrorCode::InvalidOrder);
         
-        // Burn tokens
-        token::burn(
+        token::transfer(
             CpiContext::new(
                 ctx.accounts.token_program.to_account_info(),
-                token::Burn {
-                    mint: ctx.accounts.token_mint.to_account_info(),
-                    from: ctx.accounts.seller_token_account.to_account_info(),
-                    authority: ctx.accounts.seller.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    to: ctx.accounts.escrow.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
                 },
             ),
             token_amount,
         )?;
         
-        // Transfer SOL back
-        let vault_bump = &[ctx.bumps.program_vault];
-        let vault_seeds: &[&[u8]] = &[b"vault", vault_bump];
-        let vault_signer = &[vault_seeds];
+        // The fill can't charge the owner, so their profile must exist already
+        let profile = &mut ctx.accounts.owner_profile;
+        profile.wallet = ctx.accounts.owner.key();
+        profile.bump = ctx.bumps.owner_profile;
         
-        anchor_lang::system_program::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.system_program.to_account_info(),
-                anchor_lang::system_program::Transfer {
-                    from: ctx.accounts.program_vault.to_account_info(),
-                    to: ctx.accounts.seller.to_account_info(),
-                },
-                vault_signer,
-            ),
-            sol_amount,
+        let order = &mut ctx.accounts.sell_order;
+        order.owner = ctx.accounts.owner.key();
+        order.id = id;
+        order.token_amount = token_amount;
+        order.trigger_price = trigger_price;
+        order.direction = direction;
+        order.min_sol_out = min_sol_out;
+        order.expiry = expiry;
+        order.bump = ctx.bumps.sell_order;
+        
+        msg!("Sell order placed: {} tokens, {:?} at {} until {}", token_amount, direction, trigger_price, expiry);
+        Ok(())
+    }
+
+    /// Close a sell order and return its tokens to the owner
+    pub fn cancel_sell_order(ctx: Context<CancelSellOrder>) -> Result<()> {
+        let order = &ctx.accounts.sell_order;
+        let id = order.id.to_le_bytes();
+        let seeds: &[&[u8]] = &[b"sell-order", order.owner.as_ref(), &id, &[order.bump]];
+        release_sell_escrow(
+            &ctx.accounts.escrow,
+            &ctx.accounts.owner_token_account,
+            &ctx.accounts.owner.to_account_info(),
+            &order.to_account_info(),
+            &[seeds],
+            &ctx.accounts.token_program,
         )?;
         
-        // Update state
-        state.total_burned = state.total_burned.checked_add(sell_burn).ok_or(ErrorCode::ArithmeticOverflow)?;
-        let price_decrease = sol_amount.checked_div(1_000_000_000).ok_or(ErrorCode::ArithmeticOverflow)?.max(1);
-        state.current_price = state.current_price.saturating_sub(price_decrease).max(MIN_PRICE);
+        msg!("Sell order cancelled: {} tokens returned", order.token_amount);
+        Ok(())
+    }
+
+    /// Execute a triggered sell order through the normal sell path, or return
+    /// the tokens of an expired one (anyone can call). Fills pay CRANK_REWARD
+    /// out of the proceeds.
+    pub fn execute_sell_order(ctx: Context<ExecuteSellOrder>) -> Result<()> {
+        let order = &ctx.accounts.sell_order;
+        let id = order.id.to_le_bytes();
+        let seeds: &[&[u8]] = &[b"sell-order", order.owner.as_ref(), &id, &[order.bump]];
+        let signer = &[seeds];
+        let owner = order.owner;
+        
+        if Clock::get()?.unix_timestamp >= order.expiry {
+            release_sell_escrow(
+                &ctx.accounts.escrow,
+                &ctx.accounts.owner_token_account,
+                &ctx.accounts.owner,
+                &order.to_account_info(),
+                signer,
+                &ctx.accounts.token_program,
+            )?;
+            msg!("Sell order expired: {} tokens returned", order.token_amount);
+            return Ok(());
+        }
+        
+        require!(
+            order.direction.is_triggered(ctx.accounts.program_state.current_price, order.trigger_price),
+            ErrorCode::SellOrderNotTriggered
+        );
+        guard::reject_opposite_trades(&ctx.accounts.instructions, guard::BUY_INSTRUCTIONS)?;
+        
+        let (token_amount, min_sol_out) = (order.token_amount, order.min_sol_out);
+        let sol_amount = execute_sell(
+            SellAccounts {
+                program_state: &mut ctx.accounts.program_state,
+                token_mint: &ctx.accounts.token_mint,
+                source: &ctx.accounts.escrow,
+                source_authority: ctx.accounts.sell_order.to_account_info(),
+                source_signer: signer,
+                seller_profile: &mut ctx.accounts.owner_profile,
+                seller_profile_bump: ctx.bumps.owner_profile,
+                stake_pool: &mut ctx.accounts.stake_pool,
+                stats: &mut ctx.accounts.stats,
+                price_oracle: &mut ctx.accounts.price_oracle,
+                program_vault: &ctx.accounts.program_vault,
+                token_program: &ctx.accounts.token_program,
+            },
+            owner,
+            token_amount,
+        )?;
+        let owner_amount = sol_amount.checked_sub(CRANK_REWARD).ok_or(ErrorCode::SlippageExceeded)?;
+        require!(owner_amount >= min_sol_out, ErrorCode::SlippageExceeded);
+        
+        move_lamports(&ctx.accounts.program_vault, &ctx.accounts.cranker.to_account_info(), CRANK_REWARD)?;
+        move_lamports(&ctx.accounts.program_vault, &ctx.accounts.owner, owner_amount)?;
+        // The escrow is empty now; its rent goes back to the owner
+        token::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            token::CloseAccount {
+                account: ctx.accounts.escrow.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.sell_order.to_account_info(),
+            },
+            signer,
+        ))?;
         
-        msg!("Sell: {} tokens for {} lamports", token_amount, sol_amount);
+        emit!(SellOrderExecuted {
+            order: ctx.accounts.sell_order.key(),
+            owner,
+            token_amount,
+            sol_amount: owner_amount,
+            cranker: ctx.accounts.cranker.key(),
+        });
+        Ok(())
+    }
+
+    /// Spend a share of the vault's surplus SOL buying on the curve and burn
+    /// what it buys (anyone can call, at most once per interval)
+    pub fn buyback_and_burn(ctx: Context<BuybackAndBurn>) -> Result<()> {
+        let clock = Clock::get()?;
+        let state = &mut ctx.accounts.program_state;
+        
+        require!(state.buyback.is_due(clock.unix_timestamp), ErrorCode::BuybackTooSoon);
+        guard::reject_opposite_trades(&ctx.accounts.instructions, guard::SELL_INSTRUCTIONS)?;
+        
+        let rent_floor = Rent::get()?.minimum_balance(ctx.accounts.program_vault.data_len());
+        let surplus = buyback::surplus(
+            ctx.accounts.program_vault.lamports(),
+            rent_floor,
+            state.sol_reserve,
+            state.dev_fees_accrued.saturating_add(state.staking_sol_owed),
+        );
+        let sol_amount = state.buyback.amount(surplus)?;
+        require!(sol_amount > 0, ErrorCode::NothingToBuyBack);
+        
+        // No fees and nothing minted: the bought tokens are burned implicitly and
+        // the SOL, already in the vault, moves from surplus into the reserve
+        let fill = state.curve.buy(&state.curve_state(ctx.accounts.token_mint.decimals), sol_amount)?;
+        let tokens_burned = fill.amount_out;
+        require!(tokens_burned > 0, ErrorCode::NothingToBuyBack);
+        
+        state.sol_reserve = state.sol_reserve.checked_add(sol_amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        state.curve_supply = state.curve_supply.checked_add(tokens_burned).ok_or(ErrorCode::ArithmeticOverflow)?;
+        state.total_burned = state.total_burned.checked_add(tokens_burned).ok_or(ErrorCode::ArithmeticOverflow)?;
+        state.total_bought_back = state.total_bought_back.checked_add(tokens_burned).ok_or(ErrorCode::ArithmeticOverflow)?;
+        state.supply.record_implicit_burn(tokens_burned)?;
+        state.buyback.spend(sol_amount, clock.unix_timestamp);
+        let price_before = state.current_price;
+        state.current_price = fill.price_after;
+        state.observe_price(price_before, clock.unix_timestamp)?;
+        ctx.accounts.stats.observe_price(state.current_price);
+        ctx.accounts.price_oracle.ring.record(state.current_price, clock.unix_timestamp)?;
+        
+        emit!(BuybackExecuted {
+            sol_amount,
+            tokens_burned,
+            price: state.current_price,
+        });
+        
+        msg!("Buyback: {} lamports of {} surplus burned {} tokens", sol_amount, surplus, tokens_burned);
         Ok(())
     }
 
+    /// Report the supply ledger next to the mint's actual supply (read-only)
+    pub fn reconcile_supply(ctx: Context<ReconcileSupply>) -> Result<SupplyReport> {
+        let report = ctx.accounts.program_state.supply.reconcile(ctx.accounts.token_mint.supply)?;
+        
+        msg!(
+            "Supply: {} circulating by ledger, {} on the mint | reconciled: {}",
+            report.circulating,
+            report.mint_supply,
+            report.reconciled
+        );
+        Ok(report)
+    }
+
     /// Airdrop tokens to ONE eligible user per cycle (level 2+ = holding MIN_AIRDROP_ELIGIBLE tokens)
     /// Authority calls this once per cycle to award a single winner
     pub fn airdrop(ctx: Context<Airdrop>) -> Result<()> {
@@ -303,15 +712,13 @@ pub mod fossr {
 
         let state = &mut ctx.accounts.program_state;
 
-        // Only authority can execute
-        require_keys_eq!(ctx.accounts.authority.key(), state.authority, ErrorCode::Unauthorized);
-
         // Check if airdrop already executed this cycle
         require!(!state.airdrop_executed, ErrorCode::AirdropAlreadyExecuted);
 
         // Check recipient is eligible (holds enough tokens = level 2+)
+        let min_eligible = math::whole_tokens(MIN_AIRDROP_ELIGIBLE, ctx.accounts.token_mint.decimals)?;
         require!(
-            ctx.accounts.recipient_token_account.amount >= MIN_AIRDROP_ELIGIBLE,
+            ctx.accounts.recipient_token_account.amount >= min_eligible,
             ErrorCode::NotEligibleForAirdrop
         );
 
@@ -346,6 +753,8 @@ pub mod fossr {
         state.airdrop_executed = true;
         state.last_airdrop_cycle = clock.unix_timestamp;
         state.airdrop_amount = 0; // Reset pot after awarding
+        state.supply.record_airdrop(amount)?;
+        ctx.accounts.stats.record_airdrop(amount)?;
 
         msg!("Airdrop: {} tokens awarded to single winner | Pot reset to 0", amount);
         Ok(())
@@ -356,8 +765,6 @@ pub mod fossr {
         let clock = Clock::get()?;
         let state = &mut ctx.accounts.program_state;
 
-        require_keys_eq!(ctx.accounts.authority.key(), state.authority, ErrorCode::Unauthorized);
-
         // Check that current cycle has ended
         require!(
             clock.unix_timestamp >= state.next_airdrop_time,
@@ -376,8 +783,6 @@ pub mod fossr {
     pub fn update_airdrop_settings(ctx: Context<UpdateSettings>, new_amount: u64) -> Result<()> {
         let state = &mut ctx.accounts.program_state;
         
-        require_keys_eq!(ctx.accounts.authority.key(), state.authority, ErrorCode::Unauthorized);
-        
         state.airdrop_amount = new_amount;
         
         msg!("Airdrop amount updated to: {}", new_amount);
@@ -388,205 +793,2193 @@ pub mod fossr {
     pub fn update_token_mint(ctx: Context<UpdateTokenMint>) -> Result<()> {
         let state = &mut ctx.accounts.program_state;
         
-        require_keys_eq!(ctx.accounts.authority.key(), state.authority, ErrorCode::Unauthorized);
+        require!(ctx.accounts.new_token_mint.decimals <= MAX_TOKEN_DECIMALS, ErrorCode::InvalidMintDecimals);
         
         state.token_mint = ctx.accounts.new_token_mint.key();
+        // The ledger follows the mint; the old one's history no longer applies
+        state.supply = SupplyLedger::new(ctx.accounts.new_token_mint.supply);
         
         msg!("Token mint updated to: {}", ctx.accounts.new_token_mint.key());
         Ok(())
     }
-}
 
-/// Calculate next airdrop time aligned to 5-minute intervals
-fn calculate_next_airdrop_time(current_time: i64) -> i64 {
-    // Align to 5-minute (300 second) intervals
-    let remainder = current_time % AIRDROP_INTERVAL;
-    if remainder == 0 {
-        current_time + AIRDROP_INTERVAL
-    } else {
-        current_time + (AIRDROP_INTERVAL - remainder)
+    /// Configure the sell circuit breaker (authority only). A zero drop disables it.
+    pub fn configure_circuit_breaker(
+        ctx: Context<UpdateSettings>,
+        max_drop_bps: u32,
+        window: i64,
+        cooldown: i64,
+    ) -> Result<()> {
+        let clock = Clock::get()?;
+        let state = &mut ctx.accounts.program_state;
+        
+        // Keep an active halt; the rolling high restarts from the current price
+        let halted_until = state.circuit_breaker.halted_until;
+        state.circuit_breaker = CircuitBreaker::new(max_drop_bps, window, cooldown)?;
+        state.reset_circuit_breaker(clock.unix_timestamp);
+        state.circuit_breaker.halted_until = halted_until;
+        
+        msg!("Circuit breaker: {} bps drop within {}s halts sells for {}s", max_drop_bps, window, cooldown);
+        Ok(())
     }
-}
-
-fn calculate_lock_duration(sol_amount: u64, timestamp: i64, slot: u64, slot_hashes_data: &[u8]) -> Result<i64> {
-    let (min, max) = if sol_amount < TIER1_THRESHOLD {
-        (TIER1_MIN, TIER1_MAX)
-    } else if sol_amount < TIER2_THRESHOLD {
-        (TIER2_MIN, TIER2_MAX)
-    } else if sol_amount < TIER3_THRESHOLD {
-        (TIER3_MIN, TIER3_MAX)
-    } else {
-        (TIER4_MIN, TIER4_MAX)
-    };
-    
-    let range = max - min;
-    let hash_seed = if slot_hashes_data.len() >= 8 {
-        u64::from_le_bytes(slot_hashes_data[0..8].try_into().unwrap())
-    } else {
-        timestamp as u64
-    };
-    
-    let combined = hash_seed.wrapping_add(timestamp as u64).wrapping_add(slot);
-    let offset = (combined % (range as u64)) as i64;
-    
-    Ok(min + offset)
-}
 
-// ============= ACCOUNTS =============
+    /// Hand the guardian role to another key (authority only)
+    pub fn set_guardian(ctx: Context<UpdateSettings>, new_guardian: Pubkey) -> Result<()> {
+        let state = &mut ctx.accounts.program_state;
+        
+        state.guardian = new_guardian;
+        
+        msg!("Guardian updated to: {}", new_guardian);
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct Initialize<'info> {
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + ProgramState::INIT_SPACE,
-        seeds = [b"program-state"],
-        bump
-    )]
-    pub program_state: Account<'info, ProgramState>,
-    
-    /// CHECK: Token mint pubkey
-    pub token_mint: AccountInfo<'info>,
-    
-    /// CHECK: Program vault PDA that holds SOL
-    #[account(mut, seeds = [b"vault"], bump)]
-    pub program_vault: AccountInfo<'info>,
+    /// Cap how much a wallet can sell per window (authority only). Zero disables a cap.
+    pub fn configure_sell_limits(
+        ctx: Context<UpdateSettings>,
+        max_tokens: u64,
+        max_supply_bps: u32,
+        window: i64,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.program_state;
+        
+        state.sell_limits = SellLimits::new(max_tokens, max_supply_bps, window)?;
+        
+        msg!("Sell limits: {} tokens / {} bps of supply per {}s", max_tokens, max_supply_bps, window);
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
-    
-    pub system_program: Program<'info, System>,
-}
+    /// Cap per-wallet buys per window and holdings, with stricter caps during launch (authority only)
+    pub fn configure_buy_limits(ctx: Context<UpdateSettings>, limits: BuyLimits) -> Result<()> {
+        limits.validate()?;
+        let state = &mut ctx.accounts.program_state;
+        
+        state.buy_limits = limits;
+        
+        msg!(
+            "Buy limits: {} lamports per {}s, max holding {} | launch: {} lamports, max holding {} for {}s",
+            limits.max_sol,
+            limits.window,
+            limits.max_holding,
+            limits.launch_max_sol,
+            limits.launch_max_holding,
+            limits.launch_duration
+        );
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct InitializeVault<'info> {
-    #[account(mut)]
-    pub program_state: Account<'info, ProgramState>,
+    /// Set the share of surplus vault SOL each buyback spends, a per-call cap
+    /// (zero for none), the minimum interval between calls and the share of
+    /// each buy's dev fee set aside to fund them (authority only)
+    pub fn configure_buyback(
+        ctx: Context<UpdateSettings>,
+        share_bps: u32,
+        max_sol: u64,
+        min_interval: i64,
+        fee_share_bps: u32,
+    ) -> Result<()> {
+        let state = &mut ctx.accounts.program_state;
+        
+        let previous = state.buyback;
+        state.buyback = Buyback::new(share_bps, max_sol, min_interval, fee_share_bps)?;
+        state.buyback.last_run = previous.last_run;
+        state.buyback.accrued = previous.accrued;
+        
+        msg!(
+            "Buyback: {} bps of surplus, up to {} lamports, every {}s, funded by {} bps of dev fees",
+            share_bps,
+            max_sol,
+            min_interval,
+            fee_share_bps
+        );
+        Ok(())
+    }
 
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    /// Set the share of the raffle fee paid to referrers, out of BASIS_POINTS (authority only)
+    pub fn configure_referrals(ctx: Context<UpdateSettings>, share_bps: u32) -> Result<()> {
+        require!(share_bps <= BASIS_POINTS, ErrorCode::InvalidLimitConfig);
+        let state = &mut ctx.accounts.program_state;
+        
+        state.referral_share_bps = share_bps;
+        
+        msg!("Referral share set to {} bps of the raffle fee", share_bps);
+        Ok(())
+    }
+
+    /// Mint a referrer's accrued rewards to their token account
+    pub fn claim_referral_rewards(ctx: Context<ClaimReferralRewards>) -> Result<()> {
+        let amount = ctx.accounts.referrer_rewards.pending;
+        require!(amount > 0, ErrorCode::NothingToClaim);
+        
+        let bump = &[ctx.accounts.program_state.bump];
+        let seeds: &[&[u8]] = &[b"program-state", bump];
+        let signer = &[seeds];
+        
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::MintTo {
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: ctx.accounts.referrer_token_account.to_account_info(),
+                    authority: ctx.accounts.program_state.to_account_info(),
+                },
+                signer,
+            ),
+            amount,
+        )?;
+        ctx.accounts.program_state.supply.record_mint(amount)?;
+        
+        let rewards = &mut ctx.accounts.referrer_rewards;
+        rewards.pending = 0;
+        rewards.total_claimed = rewards.total_claimed.checked_add(amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        
+        msg!("Referral rewards claimed: {} tokens", amount);
+        Ok(())
+    }
+
+    /// Create the trade statistics account (authority only). Trades require it,
+    /// so it has to exist before the first buy; see `initialize`.
+    pub fn initialize_stats(ctx: Context<InitializeStats>) -> Result<()> {
+        let stats = &mut ctx.accounts.stats;
+        
+        stats.all_time_high = ctx.accounts.program_state.current_price;
+        stats.all_time_low = ctx.accounts.program_state.current_price;
+        stats.bump = ctx.bumps.stats;
+        
+        msg!("Stats initialized | Price: {}", stats.all_time_high);
+        Ok(())
+    }
+
+    /// Create the price observation ring, starting from the current price (authority only)
+    pub fn initialize_oracle(ctx: Context<InitializeOracle>) -> Result<()> {
+        let oracle = &mut ctx.accounts.price_oracle;
+        
+        oracle.ring = ObservationRing::new(ctx.accounts.program_state.current_price, Clock::get()?.unix_timestamp);
+        oracle.bump = ctx.bumps.price_oracle;
+        
+        msg!("Price oracle initialized | Price: {}", oracle.ring.last_price);
+        Ok(())
+    }
+
+    /// Time-weighted average price over the last `window` seconds, in lamports
+    /// per token * PRICE_SCALE. Read-only, so other programs can CPI into it.
+    pub fn get_twap(ctx: Context<GetTwap>, window: i64) -> Result<u128> {
+        let twap = ctx.accounts.price_oracle.ring.twap(window, Clock::get()?.unix_timestamp)?;
+        
+        msg!("TWAP over {}s: {}", window, twap);
+        Ok(twap)
+    }
+
+    /// Create the staking pool and its token vault (authority only)
+    pub fn initialize_staking(ctx: Context<InitializeStaking>, fee_share_bps: u32, unstake_cooldown: i64) -> Result<()> {
+        require!(fee_share_bps <= BASIS_POINTS && unstake_cooldown >= 0, ErrorCode::InvalidLimitConfig);
+        let pool = &mut ctx.accounts.stake_pool;
+        
+        pool.fee_share_bps = fee_share_bps;
+        pool.unstake_cooldown = unstake_cooldown;
+        pool.total_staked = 0;
+        pool.token_rewards = RewardAccumulator::default();
+        pool.sol_rewards = RewardAccumulator::default();
+        pool.bump = ctx.bumps.stake_pool;
+        
+        msg!("Staking initialized | {} bps of dev and sell fees to stakers | Cooldown {}s", fee_share_bps, unstake_cooldown);
+        Ok(())
+    }
+
+    /// Change the stakers' fee share and unstake cooldown (authority only)
+    pub fn configure_staking(ctx: Context<ConfigureStaking>, fee_share_bps: u32, unstake_cooldown: i64) -> Result<()> {
+        require!(fee_share_bps <= BASIS_POINTS && unstake_cooldown >= 0, ErrorCode::InvalidLimitConfig);
+        let pool = &mut ctx.accounts.stake_pool;
+        
+        pool.fee_share_bps = fee_share_bps;
+        pool.unstake_cooldown = unstake_cooldown;
+        
+        msg!("Staking updated | {} bps of dev and sell fees to stakers | Cooldown {}s", fee_share_bps, unstake_cooldown);
+        Ok(())
+    }
+
+    /// Stake tokens into the pool vault
+    pub fn stake(ctx: Context<Stake>, amount: u64) -> Result<()> {
+        require!(amount > 0, ErrorCode::InvalidAmount);
+        
+        token::transfer(
+            CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.owner_token_account.to_account_info(),
+                    to: ctx.accounts.stake_vault.to_account_info(),
+                    authority: ctx.accounts.owner.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        
+        let pool = &mut ctx.accounts.stake_pool;
+        let stake = &mut ctx.accounts.stake_account;
+        stake.settle(pool)?;
+        stake.owner = ctx.accounts.owner.key();
+        stake.amount = stake.amount.checked_add(amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        stake.last_staked = Clock::get()?.unix_timestamp;
+        stake.bump = ctx.bumps.stake_account;
+        pool.total_staked = pool.total_staked.checked_add(amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        // Rewards that arrived while nobody was staked go to the first stakers
+        pool.distribute_queued()?;
+        
+        msg!("Staked {} tokens | Pool total {}", amount, pool.total_staked);
+        Ok(())
+    }
+
+    /// Withdraw staked tokens once the cooldown since the last stake has passed
+    pub fn unstake(ctx: Context<Unstake>, amount: u64) -> Result<()> {
+        let pool = &mut ctx.accounts.stake_pool;
+        let stake = &mut ctx.accounts.stake_account;
+        
+        require!(amount > 0 && amount <= stake.amount, ErrorCode::InvalidAmount);
+        let unlocked_at = stake.last_staked.saturating_add(pool.unstake_cooldown);
+        require!(Clock::get()?.unix_timestamp >= unlocked_at, ErrorCode::StakeCooldown);
+        
+        stake.settle(pool)?;
+        stake.amount -= amount;
+        pool.total_staked = pool.total_staked.checked_sub(amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        
+        let bump = &[pool.bump];
+        let seeds: &[&[u8]] = &[b"stake-pool", bump];
+        let signer = &[seeds];
+        
+        token::transfer(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::Transfer {
+                    from: ctx.accounts.stake_vault.to_account_info(),
+                    to: ctx.accounts.owner_token_account.to_account_info(),
+                    authority: pool.to_account_info(),
+                },
+                signer,
+            ),
+            amount,
+        )?;
+        
+        msg!("Unstaked {} tokens | Pool total {}", amount, pool.total_staked);
+        Ok(())
+    }
+
+    /// Pay a staker's accrued fee rewards: their dev fee share in SOL from the
+    /// vault and their sell fee share minted to their token account
+    pub fn claim_staking_rewards(ctx: Context<ClaimStakingRewards>) -> Result<()> {
+        let stake = &mut ctx.accounts.stake_account;
+        stake.settle(&ctx.accounts.stake_pool)?;
+        let (tokens, lamports) = (stake.pending, stake.pending_sol);
+        require!(tokens > 0 || lamports > 0, ErrorCode::NothingToClaim);
+        stake.pending = 0;
+        stake.pending_sol = 0;
+        
+        if tokens > 0 {
+            let bump = &[ctx.accounts.program_state.bump];
+            let seeds: &[&[u8]] = &[b"program-state", bump];
+            let signer = &[seeds];
+            
+            token::mint_to(
+                CpiContext::new_with_signer(
+                    ctx.accounts.token_program.to_account_info(),
+                    token::MintTo {
+                        mint: ctx.accounts.token_mint.to_account_info(),
+                        to: ctx.accounts.owner_token_account.to_account_info(),
+                        authority: ctx.accounts.program_state.to_account_info(),
+                    },
+                    signer,
+                ),
+                tokens,
+            )?;
+            ctx.accounts.program_state.supply.record_mint(tokens)?;
+        }
+        if lamports > 0 {
+            let state = &mut ctx.accounts.program_state;
+            state.staking_sol_owed = state.staking_sol_owed.checked_sub(lamports).ok_or(ErrorCode::ArithmeticOverflow)?;
+            move_lamports(&ctx.accounts.program_vault, &ctx.accounts.owner.to_account_info(), lamports)?;
+        }
+        
+        msg!("Staking rewards claimed: {} tokens, {} lamports", tokens, lamports);
+        Ok(())
+    }
+
+    /// Create the vesting pool with the fixed number of tokens all schedules
+    /// together may ever vest (authority only)
+    pub fn initialize_vesting(ctx: Context<InitializeVesting>, allocation: u64) -> Result<()> {
+        require!(allocation > 0, ErrorCode::InvalidVesting);
+        let pool = &mut ctx.accounts.vesting_pool;
+        
+        pool.allocation = allocation;
+        pool.total_allocated = 0;
+        pool.bump = ctx.bumps.vesting_pool;
+        
+        msg!("Vesting initialized | Allocation: {} tokens", allocation);
+        Ok(())
+    }
+
+    /// Allocate `total` tokens to `beneficiary`, vesting linearly over `duration`
+    /// seconds from now with nothing claimable before `cliff` (authority only)
+    pub fn create_vesting(
+        ctx: Context<CreateVesting>,
+        beneficiary: Pubkey,
+        id: u64,
+        total: u64,
+        cliff: i64,
+        duration: i64,
+        revocable: bool,
+    ) -> Result<()> {
+        vesting::validate(total, cliff, duration)?;
+        let pool = &mut ctx.accounts.vesting_pool;
+        pool.total_allocated = vesting::allocate(pool.total_allocated, total, pool.allocation)?;
+        let schedule = &mut ctx.accounts.vesting_schedule;
+        
+        schedule.beneficiary = beneficiary;
+        schedule.id = id;
+        schedule.total = total;
+        schedule.claimed = 0;
+        schedule.start = Clock::get()?.unix_timestamp;
+        schedule.cliff = cliff;
+        schedule.duration = duration;
+        schedule.revocable = revocable;
+        schedule.revoked = false;
+        schedule.bump = ctx.bumps.vesting_schedule;
+        
+        emit!(VestingCreated {
+            schedule: schedule.key(),
+            beneficiary,
+            total,
+            start: schedule.start,
+            cliff,
+            duration,
+            revocable,
+        });
+        msg!("Vesting created: {} tokens for {} over {}s (cliff {}s)", total, beneficiary, duration, cliff);
+        Ok(())
+    }
+
+    /// Mint everything vested and not yet claimed to the beneficiary
+    pub fn claim_vested(ctx: Context<ClaimVested>) -> Result<()> {
+        let schedule = &mut ctx.accounts.vesting_schedule;
+        let vested = schedule.vested_at(Clock::get()?.unix_timestamp)?;
+        let amount = vested.saturating_sub(schedule.claimed);
+        require!(amount > 0, ErrorCode::NothingToClaim);
+        schedule.claimed = vested;
+        
+        let bump = &[ctx.accounts.program_state.bump];
+        let seeds: &[&[u8]] = &[b"program-state", bump];
+        let signer = &[seeds];
+        
+        token::mint_to(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                token::MintTo {
+                    mint: ctx.accounts.token_mint.to_account_info(),
+                    to: ctx.accounts.beneficiary_token_account.to_account_info(),
+                    authority: ctx.accounts.program_state.to_account_info(),
+                },
+                signer,
+            ),
+            amount,
+        )?;
+        ctx.accounts.program_state.supply.record_mint(amount)?;
+        
+        msg!("Vested tokens claimed: {} | {} of {} claimed", amount, vested, ctx.accounts.vesting_schedule.total);
+        Ok(())
+    }
+
+    /// Cancel the unvested part of a revocable schedule (authority only).
+    /// Tokens vested so far stay claimable by the beneficiary; cancelled
+    /// tokens return to the vesting allocation.
+    pub fn revoke_vesting(ctx: Context<RevokeVesting>) -> Result<()> {
+        let schedule = &mut ctx.accounts.vesting_schedule;
+        require!(schedule.revocable, ErrorCode::VestingNotRevocable);
+        require!(!schedule.revoked, ErrorCode::VestingNotRevocable);
+        
+        let vested = schedule.vested_at(Clock::get()?.unix_timestamp)?;
+        let cancelled = schedule.total - vested;
+        schedule.total = vested;
+        schedule.revoked = true;
+        let pool = &mut ctx.accounts.vesting_pool;
+        pool.total_allocated = pool.total_allocated.checked_sub(cancelled).ok_or(ErrorCode::ArithmeticOverflow)?;
+        
+        emit!(VestingRevoked {
+            schedule: schedule.key(),
+            beneficiary: schedule.beneficiary,
+            vested,
+            cancelled,
+        });
+        msg!("Vesting revoked: {} vested, {} cancelled", vested, cancelled);
+        Ok(())
+    }
+
+    /// Set the dev fee recipients and their shares, which must sum to BASIS_POINTS (authority only)
+    pub fn configure_fee_splitter(ctx: Context<ConfigureFeeSplitter>, recipients: Vec<FeeRecipient>) -> Result<()> {
+        require!(!recipients.is_empty() && recipients.len() <= MAX_FEE_RECIPIENTS, ErrorCode::InvalidFeeSplit);
+        let mut total_bps: u64 = 0;
+        for (i, recipient) in recipients.iter().enumerate() {
+            require!(recipient.share_bps > 0, ErrorCode::InvalidFeeSplit);
+            require!(
+                recipients[..i].iter().all(|other| other.wallet != recipient.wallet),
+                ErrorCode::InvalidFeeSplit
+            );
+            total_bps += recipient.share_bps as u64;
+        }
+        require!(total_bps == BASIS_POINTS as u64, ErrorCode::InvalidFeeSplit);
+        
+        let splitter = &mut ctx.accounts.fee_splitter;
+        splitter.recipients = recipients;
+        splitter.bump = ctx.bumps.fee_splitter;
+        
+        msg!("Fee split updated: {} recipients", splitter.recipients.len());
+        Ok(())
+    }
+
+    /// Pay out accrued dev fees to the split's recipients (anyone can call).
+    /// Recipient wallets are passed as remaining accounts in split order.
+    pub fn distribute_fees<'info>(ctx: Context<'_, '_, 'info, 'info, DistributeFees<'info>>) -> Result<()> {
+        let splitter = &ctx.accounts.fee_splitter;
+        let wallets = ctx.remaining_accounts;
+        require!(wallets.len() == splitter.recipients.len(), ErrorCode::InvalidFeeSplit);
+        
+        let accrued = ctx.accounts.program_state.dev_fees_accrued;
+        require!(accrued > 0, ErrorCode::NothingToClaim);
+        
+        // Shares round down; the dust stays accrued for the next distribution
+        let mut paid: u64 = 0;
+        for (recipient, wallet) in splitter.recipients.iter().zip(wallets) {
+            require_keys_eq!(recipient.wallet, wallet.key(), ErrorCode::InvalidFeeSplit);
+            let share = math::mul_div(accrued, recipient.share_bps as u64, BASIS_POINTS as u64, math::Rounding::Down)?;
+            move_lamports(&ctx.accounts.program_vault, wallet, share)?;
+            paid += share;
+        }
+        
+        let state = &mut ctx.accounts.program_state;
+        state.dev_fees_accrued -= paid;
+        let splitter = &mut ctx.accounts.fee_splitter;
+        splitter.total_distributed = splitter.total_distributed.checked_add(paid).ok_or(ErrorCode::ArithmeticOverflow)?;
+        
+        msg!("Dev fees distributed: {} lamports to {} recipients", paid, wallets.len());
+        Ok(())
+    }
+
+    /// Exempt a market-maker wallet from sell limits, or revoke it (authority only)
+    pub fn set_market_maker(ctx: Context<SetMarketMaker>, wallet: Pubkey, exempt: bool) -> Result<()> {
+        let profile = &mut ctx.accounts.profile;
+        
+        profile.wallet = wallet;
+        profile.market_maker = exempt;
+        profile.bump = ctx.bumps.profile;
+        
+        msg!("Market maker {}: {}", wallet, exempt);
+        Ok(())
+    }
+
+    /// Lift a circuit breaker halt before its cooldown ends (guardian only)
+    pub fn resume_trading(ctx: Context<ResumeTrading>) -> Result<()> {
+        let clock = Clock::get()?;
+        let state = &mut ctx.accounts.program_state;
+        
+        state.reset_circuit_breaker(clock.unix_timestamp);
+        
+        emit!(TradingResumed {
+            guardian: ctx.accounts.guardian.key(),
+            price: state.current_price,
+        });
+        msg!("Trading resumed by guardian");
+        Ok(())
+    }
+}
+
+/// The accounts every buy touches, however its SOL reaches the vault
+struct BuyAccounts<'a, 'info> {
+    program_state: &'a mut Account<'info, ProgramState>,
+    token_mint: &'a Account<'info, Mint>,
+    buyer_token_account: &'a Account<'info, TokenAccount>,
+    buyer_profile: &'a mut Account<'info, BuyerProfile>,
+    buyer_profile_bump: u8,
+    purchase_order: &'a mut Account<'info, PurchaseOrder>,
+    purchase_order_bump: u8,
+    stake_pool: &'a mut Account<'info, StakePool>,
+    stats: &'a mut Account<'info, ProtocolStats>,
+    price_oracle: &'a mut Account<'info, PriceOracle>,
+    slot_hashes: &'a AccountInfo<'info>,
+    token_program: &'a Program<'info, Token>,
+    referrer_profile: Option<&'a Account<'info, BuyerProfile>>,
+    referrer_rewards: Option<&'a mut Account<'info, ReferrerRewards>>,
+    referrer_rewards_bump: Option<u8>,
+}
+
+/// Pay `sol_amount` lamports from the buyer into the vault and fill the buy
+/// for the beneficiary. Returns the tokens minted to the beneficiary.
+fn buy_with(accounts: &mut BuyTokens<'_>, bumps: &BuyTokensBumps, sol_amount: u64) -> Result<u64> {
+    // Gifts need the beneficiary's allowance, since they spend the
+    // beneficiary's buy caps and restart their sell timers. Only the
+    // beneficiary can bind their own referrer; a gift may only credit the one
+    // already bound.
+    let beneficiary = accounts.beneficiary.key();
+    if beneficiary != accounts.buyer.key() {
+        require!(accounts.gift_allowance.is_some(), ErrorCode::GiftNotAllowed);
+        if let Some(referrer_profile) = &accounts.referrer_profile {
+            require_keys_eq!(accounts.buyer_profile.referrer, referrer_profile.wallet, ErrorCode::InvalidReferrer);
+        }
+    }
+    
+    // Transfer SOL to program vault
+    anchor_lang::system_program::transfer(
+        CpiContext::new(
+            accounts.system_program.to_account_info(),
+            anchor_lang::system_program::Transfer {
+                from: accounts.buyer.to_account_info(),
+                to: accounts.program_vault.to_account_info(),
+            },
+        ),
+        sol_amount,
+    )?;
+    
+    execute_buy(
+        BuyAccounts {
+            program_state: &mut accounts.program_state,
+            token_mint: &accounts.token_mint,
+            buyer_token_account: &accounts.buyer_token_account,
+            buyer_profile: &mut accounts.buyer_profile,
+            buyer_profile_bump: bumps.buyer_profile,
+            purchase_order: &mut accounts.purchase_order,
+            purchase_order_bump: bumps.purchase_order,
+            stake_pool: &mut accounts.stake_pool,
+            stats: &mut accounts.stats,
+            price_oracle: &mut accounts.price_oracle,
+            slot_hashes: &accounts.slot_hashes,
+            token_program: &accounts.token_program,
+            referrer_profile: accounts.referrer_profile.as_ref(),
+            referrer_rewards: accounts.referrer_rewards.as_mut(),
+            referrer_rewards_bump: bumps.referrer_rewards,
+        },
+        beneficiary,
+        sol_amount,
+    )
+}
+
+/// Tokens a buy of `sol_amount` lamports delivers after the dev, burn and
+/// raffle fees; 0 if the fees swallow the whole fill
+fn buy_output(curve: &CurveKind, curve_state: &CurveState, sol_amount: u64) -> Result<u64> {
+    let dev_fee = math::fee(sol_amount, DEV_FEE_BPS, math::Rounding::Up)?;
+    let fill = curve.buy(curve_state, sol_amount - dev_fee)?;
+    Ok(math::split_buy(fill.amount_out).map_or(0, |amounts| amounts.tokens_to_buyer))
+}
+
+/// A buy within the buy limits that delivers at least `token_amount` where
+/// one lamport less does not. Delivery is only monotone to within one atom:
+/// `split_buy` rounds fees up, so filling one more atom can deliver one less.
+/// The binary search can therefore land just past such a dip and overshoot
+/// the true minimum by about two atoms' worth of SOL. A buy the curve can't
+/// fill counts as enough here and fails when it executes.
+fn sol_for_exact_tokens(curve: &CurveKind, curve_state: &CurveState, token_amount: u64) -> Result<u64> {
+    let enough = |sol_amount| buy_output(curve, curve_state, sol_amount).map_or(true, |tokens| tokens >= token_amount);
+    require!(enough(MAX_BUY_AMOUNT), ErrorCode::BuyAmountTooLarge);
+    
+    let (mut low, mut high) = (MIN_BUY_AMOUNT, MAX_BUY_AMOUNT);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if enough(mid) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+    Ok(low)
+}
+
+/// Fill a buy of `sol_amount` lamports for `buyer` once the SOL is in the
+/// vault: fees, curve fill, buy caps, referral credit, lock, mint and
+/// bookkeeping. Returns the tokens minted to the buyer.
+fn execute_buy(accounts: BuyAccounts<'_, '_>, buyer: Pubkey, sol_amount: u64) -> Result<u64> {
+    let clock = Clock::get()?;
+    let state = accounts.program_state;
+    
+    require!(sol_amount >= MIN_BUY_AMOUNT, ErrorCode::BuyAmountTooSmall);
+    require!(sol_amount <= MAX_BUY_AMOUNT, ErrorCode::BuyAmountTooLarge);
+    require!(state.current_price > 0, ErrorCode::InvalidPrice);
+    
+    // The dev fee (0.031%) stays in the vault for the fee splitter; the rest
+    // fills on the bonding curve, less the burn fee (0.069%) and the raffle
+    // fee (0.621%) which accumulates into the global airdrop pot
+    let dev_fee = math::fee(sol_amount, DEV_FEE_BPS, math::Rounding::Up)?;
+    let sol_to_curve = sol_amount - dev_fee;
+    let fill = state.curve.buy(&state.curve_state(accounts.token_mint.decimals), sol_to_curve)?;
+    let math::BuyAmounts {
+        tokens_before_fees,
+        burn_amount,
+        raffle_fee_amount,
+        tokens_to_buyer,
+    } = math::split_buy(fill.amount_out)?;
+    
+    // Whale caps: SOL spent per window and tokens held after the buy
+    let limits = state.buy_limits;
+    let sol_cap = limits.sol_cap(state.launch_time, clock.unix_timestamp);
+    let profile = accounts.buyer_profile;
+    profile.buy_window.record(sol_amount, sol_cap, limits.window, clock.unix_timestamp, ErrorCode::BuyLimitExceeded)?;
+    if let Some(max_holding) = limits.holding_cap(state.launch_time, clock.unix_timestamp) {
+        let holding = accounts.buyer_token_account.amount
+            .checked_add(tokens_to_buyer)
+            .ok_or(ErrorCode::ArithmeticOverflow)?;
+        require!(holding <= max_holding, ErrorCode::MaxHoldingExceeded);
+    }
+    let balance = accounts.buyer_token_account.amount;
+    let hold_start = math::effective_hold_start(balance, profile.hold_balance, profile.hold_start, clock.unix_timestamp)?;
+    profile.hold_start = math::blended_hold_start(balance, hold_start, tokens_to_buyer, clock.unix_timestamp)?;
+    profile.hold_balance = balance.checked_add(tokens_to_buyer).ok_or(ErrorCode::ArithmeticOverflow)?;
+    let first_buy = profile.last_buy_slot == 0;
+    profile.wallet = buyer;
+    profile.last_buy_slot = clock.slot;
+    profile.bump = accounts.buyer_profile_bump;
+    
+    // Referral share of the raffle fee. The referrer is bound at the wallet's
+    // first buy and must already have bought, so referrals only point back
+    // in time and can never form a cycle.
+    let referral_fee = match (accounts.referrer_profile, accounts.referrer_rewards) {
+        (Some(referrer_profile), Some(rewards)) => {
+            let referrer = referrer_profile.wallet;
+            if first_buy {
+                require_keys_neq!(referrer, profile.wallet, ErrorCode::SelfReferral);
+                require!(referrer_profile.last_buy_slot != 0, ErrorCode::InvalidReferrer);
+                profile.referrer = referrer;
+                rewards.referral_count = rewards.referral_count.checked_add(1).ok_or(ErrorCode::ArithmeticOverflow)?;
+            }
+            require_keys_eq!(profile.referrer, referrer, ErrorCode::InvalidReferrer);
+            
+            let referral_fee = math::mul_div(raffle_fee_amount, state.referral_share_bps as u64, BASIS_POINTS as u64, math::Rounding::Down)?;
+            rewards.referrer = referrer;
+            rewards.pending = rewards.pending.checked_add(referral_fee).ok_or(ErrorCode::ArithmeticOverflow)?;
+            rewards.total_earned = rewards.total_earned.checked_add(referral_fee).ok_or(ErrorCode::ArithmeticOverflow)?;
+            rewards.referred_sol = rewards.referred_sol.checked_add(sol_amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+            rewards.bump = accounts.referrer_rewards_bump.ok_or(ErrorCode::InvalidReferrer)?;
+            referral_fee
+        }
+        (None, None) => 0,
+        _ => return err!(ErrorCode::InvalidReferrer),
+    };
+    
+    // Calculate lock duration
+    let slot_hashes_data = accounts.slot_hashes.data.borrow();
+    let lock_duration = calculate_lock_duration(sol_amount, clock.unix_timestamp, clock.slot, &slot_hashes_data)?;
+    let unlock_time = clock.unix_timestamp + lock_duration;
+    
+    // Mint tokens to buyer's ATA (not locked for MVP simplicity)
+    let bump = &[state.bump];
+    let seeds: &[&[u8]] = &[b"program-state", bump];
+    let signer = &[seeds];
+    
+    token::mint_to(
+        CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token::MintTo {
+                mint: accounts.token_mint.to_account_info(),
+                to: accounts.buyer_token_account.to_account_info(),
+                authority: state.to_account_info(),
+            },
+            signer,
+        ),
+        tokens_to_buyer,
+    )?;
+    
+    // Update state (burn is implicit - we don't mint burn_amount)
+    state.sol_reserve = state.sol_reserve.checked_add(sol_to_curve).ok_or(ErrorCode::ArithmeticOverflow)?;
+    // Stakers' share of the dev fee stays in the vault until they claim it
+    let staking_reward = accounts.stake_pool.credit_dev_fee(dev_fee)?;
+    state.staking_sol_owed = state.staking_sol_owed.checked_add(staking_reward).ok_or(ErrorCode::ArithmeticOverflow)?;
+    // The buyback's share stays behind too, as surplus for the next buyback
+    let buyback_fee = state.buyback.credit(dev_fee - staking_reward)?;
+    state.dev_fees_accrued = state.dev_fees_accrued
+        .checked_add(dev_fee - staking_reward - buyback_fee)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    state.total_burned = state.total_burned.checked_add(burn_amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+    state.supply.record_mint(tokens_to_buyer)?;
+    state.supply.record_implicit_burn(burn_amount)?;
+    state.total_buys = state.total_buys.checked_add(1).ok_or(ErrorCode::ArithmeticOverflow)?;
+    // Global airdrop pot increases with each buy, less the referrer's share
+    state.airdrop_amount = state.airdrop_amount
+        .checked_add(raffle_fee_amount - referral_fee)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    
+    // Update bonding curve
+    state.curve_supply = state.curve_supply.checked_add(tokens_before_fees).ok_or(ErrorCode::ArithmeticOverflow)?;
+    let price_before = state.current_price;
+    state.current_price = fill.price_after;
+    // Buys only raise the price, so this just tracks the rolling high
+    state.observe_price(price_before, clock.unix_timestamp)?;
+    accounts.stats.record_buy(sol_amount, first_buy, state.current_price)?;
+    accounts.price_oracle.ring.record(state.current_price, clock.unix_timestamp)?;
+    
+    // Create purchase order for tracking
+    let order = accounts.purchase_order;
+    order.buyer = buyer;
+    order.sol_amount = sol_amount;
+    order.token_amount = tokens_to_buyer;
+    order.timestamp = clock.unix_timestamp;
+    order.unlock_time = unlock_time;
+    order.bump = accounts.purchase_order_bump;
+    
+    emit!(TokensBought {
+        buyer,
+        sol_amount,
+        token_amount: tokens_to_buyer,
+        ask: fill.fill_price,
+        price: state.current_price,
+        unlock_time,
+    });
+
+    msg!(
+        "Buy: {} tokens for {} lamports | Raffle pot +{} | Lock until: {}",
+        tokens_to_buyer,
+        sol_amount,
+        raffle_fee_amount,
+        unlock_time
+    );
+    Ok(tokens_to_buyer)
+}
+
+/// The accounts every sell touches, whoever holds the tokens being sold
+struct SellAccounts<'a, 'info> {
+    program_state: &'a mut Account<'info, ProgramState>,
+    token_mint: &'a Account<'info, Mint>,
+    source: &'a Account<'info, TokenAccount>,
+    source_authority: AccountInfo<'info>,
+    source_signer: &'a [&'a [&'a [u8]]],     // Seeds when the source authority is a PDA
+    seller_profile: &'a mut Account<'info, BuyerProfile>,
+    seller_profile_bump: u8,
+    stake_pool: &'a mut Account<'info, StakePool>,
+    stats: &'a mut Account<'info, ProtocolStats>,
+    price_oracle: &'a mut Account<'info, PriceOracle>,
+    program_vault: &'a AccountInfo<'info>,
+    token_program: &'a Program<'info, Token>,
+}
+
+/// Sell `token_amount` tokens from the source account for `seller`: halts,
+/// caps, fees and the early-sell tax, curve fill, burn and bookkeeping.
+/// Returns the lamports owed, which the caller pays out of the vault.
+fn execute_sell(accounts: SellAccounts<'_, '_>, seller: Pubkey, token_amount: u64) -> Result<u64> {
+    let clock = Clock::get()?;
+    let state = accounts.program_state;
+    let decimals = accounts.token_mint.decimals;
+    
+    require!(!state.circuit_breaker.is_halted(clock.unix_timestamp), ErrorCode::TradingHalted);
+    require!(token_amount >= math::whole_tokens(MIN_SELL_AMOUNT, decimals)?, ErrorCode::SellAmountTooSmall);
+    require!(accounts.source.amount >= token_amount, ErrorCode::InsufficientBalance);
+    require!(state.current_price > 0, ErrorCode::InvalidPrice);
+
+    let profile = accounts.seller_profile;
+    let sellable_from = profile.last_buy_slot.saturating_add(MIN_BUY_SELL_SLOT_GAP);
+    require!(profile.last_buy_slot == 0 || clock.slot >= sellable_from, ErrorCode::SellTooSoonAfterBuy);
+    profile.wallet = seller;
+    profile.bump = accounts.seller_profile_bump;
+
+    // Anti-dump cap on tokens sold per window; market makers are exempt but still tracked
+    let limits = state.sell_limits;
+    let cap = if profile.market_maker { None } else { limits.cap(accounts.token_mint.supply)? };
+    profile.sell_window.record(token_amount, cap, limits.window, clock.unix_timestamp, ErrorCode::SellLimitExceeded)?;
+    
+    // Calculate burn fee, the early-sell tax for recently bought tokens and
+    // the SOL the curve pays for the rest
+    let hold_start = math::effective_hold_start(
+        accounts.source.amount,
+        profile.hold_balance,
+        profile.hold_start,
+        clock.unix_timestamp,
+    )?;
+    let held_for = math::held_for(hold_start, clock.unix_timestamp);
+    profile.hold_balance = profile.hold_balance.saturating_sub(token_amount);
+    let math::SellAmounts {
+        sell_burn,
+        early_sell_tax,
+        tokens_to_convert,
+    } = math::split_sell(token_amount, math::early_sell_tax_bps(held_for))?;
+    let fill = state.curve.sell(&state.curve_state(decimals), tokens_to_convert)?;
+    let curve_amount = fill.amount_out;
+
+    // Never pay more than this seller's pro-rata share of the reserve, so the
+    // remaining holders can always be paid out of what is left
+    let reserve_share = math::reserve_share(state.sol_reserve, tokens_to_convert, accounts.token_mint.supply)?;
+    let sol_amount = curve_amount.min(reserve_share);
+    require!(sol_amount > 0 && sol_amount <= state.sol_reserve, ErrorCode::InsufficientReserve);
+
+    // The rent-exempt minimum is not part of the reserve and must stay in the vault
+    let rent_floor = Rent::get()?.minimum_balance(accounts.program_vault.data_len());
+    let withdrawable = accounts.program_vault.lamports().saturating_sub(rent_floor);
+    require!(withdrawable >= sol_amount, ErrorCode::InsufficientVaultBalance);
+    
+    // Burn tokens
+    token::burn(
+        CpiContext::new_with_signer(
+            accounts.token_program.to_account_info(),
+            token::Burn {
+                mint: accounts.token_mint.to_account_info(),
+                from: accounts.source.to_account_info(),
+                authority: accounts.source_authority,
+            },
+            accounts.source_signer,
+        ),
+        token_amount,
+    )?;
+    
+    // Update state
+    state.sol_reserve = state.sol_reserve.checked_sub(sol_amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+    // Stakers' share of the sell fee is minted to them on claim instead of burned
+    let staking_reward = accounts.stake_pool.credit_sell_fee(sell_burn)?;
+    state.total_burned = state.total_burned
+        .checked_add(sell_burn - staking_reward)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    // Every sold token is burned from the seller, not just the fee
+    state.supply.record_explicit_burn(token_amount)?;
+    // The early-sell tax is burned here and re-minted through the airdrop pot
+    state.airdrop_amount = state.airdrop_amount
+        .checked_add(early_sell_tax)
+        .ok_or(ErrorCode::ArithmeticOverflow)?;
+    // Tokens the curve never issued (airdrops) don't reduce its supply below zero
+    state.curve_supply = state.curve_supply.saturating_sub(tokens_to_convert);
+    let price_before = state.current_price;
+    state.current_price = fill.price_after;
+    accounts.stats.record_sell(sol_amount, state.current_price)?;
+    accounts.price_oracle.ring.record(state.current_price, clock.unix_timestamp)?;
+
+    if let Some(reference_price) = state.observe_price(price_before, clock.unix_timestamp)? {
+        emit!(CircuitBreakerTripped {
+            reference_price,
+            price: state.current_price,
+            halted_until: state.circuit_breaker.halted_until,
+        });
+        msg!("Circuit breaker tripped | Sells halted until {}", state.circuit_breaker.halted_until);
+    }
+
+    emit!(TokensSold {
+        seller,
+        token_amount,
+        sol_amount,
+        early_sell_tax,
+        bid: fill.fill_price,
+        price: state.current_price,
+    });
+    
+    msg!("Sell: {} tokens for {} lamports | Early-sell tax {}", token_amount, sol_amount, early_sell_tax);
+    Ok(sol_amount)
+}
+
+/// Return a sell order's escrowed tokens to its owner and close the escrow
+fn release_sell_escrow<'info>(
+    escrow: &Account<'info, TokenAccount>,
+    owner_token_account: &Account<'info, TokenAccount>,
+    owner: &AccountInfo<'info>,
+    sell_order: &AccountInfo<'info>,
+    signer: &[&[&[u8]]],
+    token_program: &Program<'info, Token>,
+) -> Result<()> {
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            token::Transfer {
+                from: escrow.to_account_info(),
+                to: owner_token_account.to_account_info(),
+                authority: sell_order.clone(),
+            },
+            signer,
+        ),
+        escrow.amount,
+    )?;
+    token::close_account(CpiContext::new_with_signer(
+        token_program.to_account_info(),
+        token::CloseAccount {
+            account: escrow.to_account_info(),
+            destination: owner.clone(),
+            authority: sell_order.clone(),
+        },
+        signer,
+    ))
+}
+
+/// Move lamports out of an account this program owns (the SOL vault, order
+/// escrows). The system program can't transfer from those, so the program
+/// debits them directly.
+fn move_lamports<'info>(from: &AccountInfo<'info>, to: &AccountInfo<'info>, lamports: u64) -> Result<()> {
+    from.sub_lamports(lamports)?;
+    to.add_lamports(lamports)?;
+    Ok(())
+}
+
+/// Calculate next airdrop time aligned to 5-minute intervals
+fn calculate_next_airdrop_time(current_time: i64) -> i64 {
+    // Align to 5-minute (300 second) intervals
+    let remainder = current_time % AIRDROP_INTERVAL;
+    if remainder == 0 {
+        current_time + AIRDROP_INTERVAL
+    } else {
+        current_time + (AIRDROP_INTERVAL - remainder)
+    }
+}
+
+fn calculate_lock_duration(sol_amount: u64, timestamp: i64, slot: u64, slot_hashes_data: &[u8]) -> Result<i64> {
+    let (min, max) = if sol_amount < TIER1_THRESHOLD {
+        (TIER1_MIN, TIER1_MAX)
+    } else if sol_amount < TIER2_THRESHOLD {
+        (TIER2_MIN, TIER2_MAX)
+    } else if sol_amount < TIER3_THRESHOLD {
+        (TIER3_MIN, TIER3_MAX)
+    } else {
+        (TIER4_MIN, TIER4_MAX)
+    };
+    
+    let range = max - min;
+    let hash_seed = if slot_hashes_data.len() >= 8 {
+        u64::from_le_bytes(slot_hashes_data[0..8].try_into().unwrap())
+    } else {
+        timestamp as u64
+    };
+    
+    let combined = hash_seed.wrapping_add(timestamp as u64).wrapping_add(slot);
+    let offset = (combined % (range as u64)) as i64;
+    
+    Ok(min + offset)
+}
+
+// ============= ACCOUNTS =============
 
+#[derive(Accounts)]
+pub struct Initialize<'info> {
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ProgramState::INIT_SPACE,
+        seeds = [b"program-state"],
+        bump
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    /// The program must be able to mint the token
+    #[account(
+        constraint = token_mint.mint_authority == COption::Some(program_state.key()) @ ErrorCode::InvalidMint,
+    )]
+    pub token_mint: Account<'info, Mint>,
+    
     /// CHECK: Program vault PDA that holds SOL
     #[account(mut, seeds = [b"vault"], bump)]
     pub program_vault: AccountInfo<'info>,
 
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateProgramState<'info> {
+    /// CHECK: Still in the legacy layout, which `LegacyProgramState::read` checks
+    #[account(mut, seeds = [b"program-state"], bump)]
+    pub program_state: UncheckedAccount<'info>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    /// CHECK: Program vault PDA that holds SOL
+    #[account(seeds = [b"vault"], bump)]
+    pub program_vault: AccountInfo<'info>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVault<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// CHECK: Program vault PDA that holds SOL
+    #[account(mut, seeds = [b"vault"], bump)]
+    pub program_vault: AccountInfo<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(amount: u64, timestamp: i64)]
+pub struct BuyTokens<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub token_mint: Account<'info, Mint>,
+    
+    /// Pays the SOL and the rent of any accounts created
+    #[account(mut)]
+    pub buyer: Signer<'info>,
+    
+    /// CHECK: Receives the tokens and owns the purchase order; the buyer itself
+    /// for a normal buy, otherwise it must have allowed gifts from the buyer
+    pub beneficiary: AccountInfo<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        associated_token::mint = token_mint,
+        associated_token::authority = beneficiary
+    )]
+    pub buyer_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        init,
+        payer = buyer,
+        space = 8 + PurchaseOrder::INIT_SPACE,
+        seeds = [b"order", beneficiary.key().as_ref(), &timestamp.to_le_bytes()],
+        bump
+    )]
+    pub purchase_order: Account<'info, PurchaseOrder>,
+    
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + BuyerProfile::INIT_SPACE,
+        seeds = [b"buyer-profile", beneficiary.key().as_ref()],
+        bump
+    )]
+    pub buyer_profile: Account<'info, BuyerProfile>,
+    
+    #[account(mut, seeds = [b"stake-pool"], bump = stake_pool.bump)]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(mut, seeds = [b"stats"], bump = stats.bump)]
+    pub stats: Account<'info, ProtocolStats>,
+    
+    #[account(mut, seeds = [b"price-oracle"], bump = price_oracle.bump)]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+    
+    /// CHECK: Program vault for SOL
+    #[account(mut, seeds = [b"vault"], bump)]
+    pub program_vault: AccountInfo<'info>,
+    
+    /// CHECK: Slot hashes for randomness
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub slot_hashes: AccountInfo<'info>,
+    
+    /// CHECK: Instructions sysvar, to reject sells in the same transaction
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+    
+    /// Referrer's profile; pass it with `referrer_rewards` to credit a referrer
+    #[account(
+        seeds = [b"buyer-profile", referrer_profile.wallet.as_ref()],
+        bump = referrer_profile.bump,
+    )]
+    pub referrer_profile: Option<Account<'info, BuyerProfile>>,
+    
+    #[account(
+        init_if_needed,
+        payer = buyer,
+        space = 8 + ReferrerRewards::INIT_SPACE,
+        seeds = [b"referrer-rewards", referrer_profile.as_ref().map(|profile| profile.wallet).unwrap_or_default().as_ref()],
+        bump
+    )]
+    pub referrer_rewards: Option<Account<'info, ReferrerRewards>>,
+    
+    /// Required when the buyer is not the beneficiary
+    #[account(
+        seeds = [b"gift-allowance", beneficiary.key().as_ref(), buyer.key().as_ref()],
+        bump = gift_allowance.bump,
+    )]
+    pub gift_allowance: Option<Account<'info, GiftAllowance>>,
+}
+
+#[derive(Accounts)]
+#[instruction(payer: Pubkey)]
+pub struct AllowGifts<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = beneficiary,
+        space = 8 + GiftAllowance::INIT_SPACE,
+        seeds = [b"gift-allowance", beneficiary.key().as_ref(), payer.as_ref()],
+        bump
+    )]
+    pub gift_allowance: Account<'info, GiftAllowance>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeGiftAllowance<'info> {
+    #[account(mut)]
+    pub beneficiary: Signer<'info>,
+    
+    #[account(
+        mut,
+        close = beneficiary,
+        has_one = beneficiary @ ErrorCode::Unauthorized,
+        seeds = [b"gift-allowance", beneficiary.key().as_ref(), gift_allowance.payer.as_ref()],
+        bump = gift_allowance.bump,
+    )]
+    pub gift_allowance: Account<'info, GiftAllowance>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct PlaceLimitBuy<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + LimitBuyOrder::INIT_SPACE,
+        seeds = [b"limit-buy", owner.key().as_ref(), &id.to_le_bytes()],
+        bump
+    )]
+    pub limit_order: Account<'info, LimitBuyOrder>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = token_mint,
+        associated_token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + BuyerProfile::INIT_SPACE,
+        seeds = [b"buyer-profile", owner.key().as_ref()],
+        bump
+    )]
+    pub owner_profile: Account<'info, BuyerProfile>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelLimitBuy<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner @ ErrorCode::Unauthorized,
+        seeds = [b"limit-buy", owner.key().as_ref(), &limit_order.id.to_le_bytes()],
+        bump = limit_order.bump,
+    )]
+    pub limit_order: Account<'info, LimitBuyOrder>,
+}
+
+#[derive(Accounts)]
+#[instruction(timestamp: i64)]
+pub struct ExecuteLimitBuy<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+    
+    /// CHECK: Order owner; receives the order account's rent when it closes
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+    
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner @ ErrorCode::Unauthorized,
+        seeds = [b"limit-buy", owner.key().as_ref(), &limit_order.id.to_le_bytes()],
+        bump = limit_order.bump,
+    )]
+    pub limit_order: Account<'info, LimitBuyOrder>,
+    
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        init,
+        payer = cranker,
+        space = 8 + PurchaseOrder::INIT_SPACE,
+        seeds = [b"order", owner.key().as_ref(), &timestamp.to_le_bytes()],
+        bump
+    )]
+    pub purchase_order: Account<'info, PurchaseOrder>,
+    
+    #[account(mut, seeds = [b"buyer-profile", owner.key().as_ref()], bump)]
+    pub owner_profile: Account<'info, BuyerProfile>,
+    
+    #[account(mut, seeds = [b"stake-pool"], bump = stake_pool.bump)]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(mut, seeds = [b"stats"], bump = stats.bump)]
+    pub stats: Account<'info, ProtocolStats>,
+    
+    #[account(mut, seeds = [b"price-oracle"], bump = price_oracle.bump)]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+    
+    /// CHECK: Program vault for SOL
+    #[account(mut, seeds = [b"vault"], bump)]
+    pub program_vault: AccountInfo<'info>,
+    
+    /// CHECK: Slot hashes for randomness
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub slot_hashes: AccountInfo<'info>,
+    
+    /// CHECK: Instructions sysvar, to reject sells in the same transaction
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct CreateDca<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + DcaSchedule::INIT_SPACE,
+        seeds = [b"dca", owner.key().as_ref(), &id.to_le_bytes()],
+        bump
+    )]
+    pub dca_schedule: Account<'info, DcaSchedule>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = token_mint,
+        associated_token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + BuyerProfile::INIT_SPACE,
+        seeds = [b"buyer-profile", owner.key().as_ref()],
+        bump
+    )]
+    pub owner_profile: Account<'info, BuyerProfile>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct StopDca<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner @ ErrorCode::Unauthorized,
+        seeds = [b"dca", owner.key().as_ref(), &dca_schedule.id.to_le_bytes()],
+        bump = dca_schedule.bump,
+    )]
+    pub dca_schedule: Account<'info, DcaSchedule>,
+}
+
+#[derive(Accounts)]
+#[instruction(timestamp: i64)]
+pub struct ExecuteDca<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+    
+    /// CHECK: Schedule owner; receives the schedule's rent once it completes
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+    
+    #[account(
+        mut,
+        has_one = owner @ ErrorCode::Unauthorized,
+        seeds = [b"dca", owner.key().as_ref(), &dca_schedule.id.to_le_bytes()],
+        bump = dca_schedule.bump,
+    )]
+    pub dca_schedule: Account<'info, DcaSchedule>,
+    
+    #[account(
+        mut,
+        associated_token::mint = token_mint,
+        associated_token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        init,
+        payer = cranker,
+        space = 8 + PurchaseOrder::INIT_SPACE,
+        seeds = [b"order", owner.key().as_ref(), &timestamp.to_le_bytes()],
+        bump
+    )]
+    pub purchase_order: Account<'info, PurchaseOrder>,
+    
+    #[account(mut, seeds = [b"buyer-profile", owner.key().as_ref()], bump)]
+    pub owner_profile: Account<'info, BuyerProfile>,
+    
+    #[account(mut, seeds = [b"stake-pool"], bump = stake_pool.bump)]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(mut, seeds = [b"stats"], bump = stats.bump)]
+    pub stats: Account<'info, ProtocolStats>,
+    
+    #[account(mut, seeds = [b"price-oracle"], bump = price_oracle.bump)]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+    
+    /// CHECK: Program vault for SOL
+    #[account(mut, seeds = [b"vault"], bump)]
+    pub program_vault: AccountInfo<'info>,
+    
+    /// CHECK: Slot hashes for randomness
+    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
+    pub slot_hashes: AccountInfo<'info>,
+    
+    /// CHECK: Instructions sysvar, to reject sells in the same transaction
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(id: u64)]
+pub struct PlaceSellOrder<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = owner_token_account.mint == token_mint.key() @ ErrorCode::InvalidMint,
+        constraint = owner_token_account.owner == owner.key() @ ErrorCode::Unauthorized,
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        init,
+        payer = owner,
+        space = 8 + SellOrder::INIT_SPACE,
+        seeds = [b"sell-order", owner.key().as_ref(), &id.to_le_bytes()],
+        bump
+    )]
+    pub sell_order: Account<'info, SellOrder>,
+    
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"sell-order-escrow", sell_order.key().as_ref()],
+        bump,
+        token::mint = token_mint,
+        token::authority = sell_order,
+    )]
+    pub escrow: Account<'info, TokenAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + BuyerProfile::INIT_SPACE,
+        seeds = [b"buyer-profile", owner.key().as_ref()],
+        bump
+    )]
+    pub owner_profile: Account<'info, BuyerProfile>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelSellOrder<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner @ ErrorCode::Unauthorized,
+        seeds = [b"sell-order", owner.key().as_ref(), &sell_order.id.to_le_bytes()],
+        bump = sell_order.bump,
+    )]
+    pub sell_order: Account<'info, SellOrder>,
+    
+    #[account(mut, seeds = [b"sell-order-escrow", sell_order.key().as_ref()], bump)]
+    pub escrow: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = owner_token_account.owner == owner.key() @ ErrorCode::Unauthorized,
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteSellOrder<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub cranker: Signer<'info>,
+    
+    /// CHECK: Order owner; receives the proceeds and the closed accounts' rent
+    #[account(mut)]
+    pub owner: AccountInfo<'info>,
+    
+    #[account(
+        mut,
+        close = owner,
+        has_one = owner @ ErrorCode::Unauthorized,
+        seeds = [b"sell-order", owner.key().as_ref(), &sell_order.id.to_le_bytes()],
+        bump = sell_order.bump,
+    )]
+    pub sell_order: Account<'info, SellOrder>,
+    
+    #[account(mut, seeds = [b"sell-order-escrow", sell_order.key().as_ref()], bump)]
+    pub escrow: Account<'info, TokenAccount>,
+    
+    /// Where an expired order's tokens are returned
+    #[account(
+        mut,
+        constraint = owner_token_account.owner == owner.key() @ ErrorCode::Unauthorized,
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    #[account(mut, seeds = [b"buyer-profile", owner.key().as_ref()], bump)]
+    pub owner_profile: Account<'info, BuyerProfile>,
+    
+    #[account(mut, seeds = [b"stake-pool"], bump = stake_pool.bump)]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(mut, seeds = [b"stats"], bump = stats.bump)]
+    pub stats: Account<'info, ProtocolStats>,
+    
+    #[account(mut, seeds = [b"price-oracle"], bump = price_oracle.bump)]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+    
+    /// CHECK: Program vault
+    #[account(mut, seeds = [b"vault"], bump)]
+    pub program_vault: AccountInfo<'info>,
+    
+    /// CHECK: Instructions sysvar, to reject buys in the same transaction
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SellTokens<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub seller: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = seller_token_account.mint == token_mint.key() @ ErrorCode::InvalidMint,
+        constraint = seller_token_account.owner == seller.key() @ ErrorCode::Unauthorized,
+    )]
+    pub seller_token_account: Account<'info, TokenAccount>,
+    
+    /// Created here for wallets that only received tokens, so the buy-to-sell
+    /// gap can't be skipped by leaving the profile out
+    #[account(
+        init_if_needed,
+        payer = seller,
+        space = 8 + BuyerProfile::INIT_SPACE,
+        seeds = [b"buyer-profile", seller.key().as_ref()],
+        bump
+    )]
+    pub seller_profile: Account<'info, BuyerProfile>,
+    
+    #[account(mut, seeds = [b"stake-pool"], bump = stake_pool.bump)]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(mut, seeds = [b"stats"], bump = stats.bump)]
+    pub stats: Account<'info, ProtocolStats>,
+    
+    #[account(mut, seeds = [b"price-oracle"], bump = price_oracle.bump)]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+    
+    /// CHECK: Program vault
+    #[account(mut, seeds = [b"vault"], bump)]
+    pub program_vault: AccountInfo<'info>,
+    
+    /// CHECK: Instructions sysvar, to reject buys in the same transaction
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct BuybackAndBurn<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    /// CHECK: Program vault holding the surplus
+    #[account(seeds = [b"vault"], bump)]
+    pub program_vault: AccountInfo<'info>,
+    
+    #[account(mut, seeds = [b"stats"], bump = stats.bump)]
+    pub stats: Account<'info, ProtocolStats>,
+    
+    #[account(mut, seeds = [b"price-oracle"], bump = price_oracle.bump)]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+    
+    /// CHECK: Instructions sysvar, to reject sells in the same transaction
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: AccountInfo<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ReconcileSupply<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub token_mint: Account<'info, Mint>,
+}
+
+#[derive(Accounts)]
+pub struct Airdrop<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = recipient_token_account.mint == token_mint.key() @ ErrorCode::InvalidMint,
+    )]
+    pub recipient_token_account: Account<'info, TokenAccount>,
+
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + LastAirdrop::INIT_SPACE,
+        seeds = [b"last-airdrop"],
+        bump
+    )]
+    pub last_airdrop: Account<'info, LastAirdrop>,
+    
+    #[account(mut, seeds = [b"stats"], bump = stats.bump)]
+    pub stats: Account<'info, ProtocolStats>,
+    
+    pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-#[instruction(sol_amount: u64, timestamp: i64)]
-pub struct BuyTokens<'info> {
+pub struct ResetAirdrop<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateSettings<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub authority: Signer<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(wallet: Pubkey)]
+pub struct SetMarketMaker<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
     #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + BuyerProfile::INIT_SPACE,
+        seeds = [b"buyer-profile", wallet.as_ref()],
+        bump
+    )]
+    pub profile: Account<'info, BuyerProfile>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimReferralRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
     pub program_state: Account<'info, ProgramState>,
     
     #[account(mut)]
     pub token_mint: Account<'info, Mint>,
     
     #[account(mut)]
-    pub buyer: Signer<'info>,
+    pub referrer: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"referrer-rewards", referrer.key().as_ref()],
+        bump = referrer_rewards.bump,
+        has_one = referrer @ ErrorCode::Unauthorized,
+    )]
+    pub referrer_rewards: Account<'info, ReferrerRewards>,
     
     #[account(
         init_if_needed,
-        payer = buyer,
+        payer = referrer,
         associated_token::mint = token_mint,
-        associated_token::authority = buyer
+        associated_token::authority = referrer
     )]
-    pub buyer_token_account: Account<'info, TokenAccount>,
+    pub referrer_token_account: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeStats<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
     
     #[account(
         init,
-        payer = buyer,
-        space = 8 + PurchaseOrder::INIT_SPACE,
-        seeds = [b"order", buyer.key().as_ref(), &timestamp.to_le_bytes()],
+        payer = authority,
+        space = 8 + ProtocolStats::INIT_SPACE,
+        seeds = [b"stats"],
         bump
     )]
-    pub purchase_order: Account<'info, PurchaseOrder>,
+    pub stats: Account<'info, ProtocolStats>,
     
-    /// CHECK: Program vault for SOL
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeOracle<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + PriceOracle::INIT_SPACE,
+        seeds = [b"price-oracle"],
+        bump
+    )]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct GetTwap<'info> {
+    #[account(seeds = [b"price-oracle"], bump = price_oracle.bump)]
+    pub price_oracle: Box<Account<'info, PriceOracle>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeStaking<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + StakePool::INIT_SPACE,
+        seeds = [b"stake-pool"],
+        bump
+    )]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(
+        init,
+        payer = authority,
+        seeds = [b"stake-vault"],
+        bump,
+        token::mint = token_mint,
+        token::authority = stake_pool,
+    )]
+    pub stake_vault: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureStaking<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub authority: Signer<'info>,
+    
+    #[account(mut, seeds = [b"stake-pool"], bump = stake_pool.bump)]
+    pub stake_pool: Account<'info, StakePool>,
+}
+
+#[derive(Accounts)]
+pub struct Stake<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = owner_token_account.mint == token_mint.key() @ ErrorCode::InvalidMint,
+        constraint = owner_token_account.owner == owner.key() @ ErrorCode::Unauthorized,
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    #[account(mut, seeds = [b"stake-pool"], bump = stake_pool.bump)]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake-vault"],
+        bump,
+        token::mint = token_mint,
+    )]
+    pub stake_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + StakeAccount::INIT_SPACE,
+        seeds = [b"stake", owner.key().as_ref()],
+        bump
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct Unstake<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    pub token_mint: Account<'info, Mint>,
+    
+    pub owner: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = owner_token_account.mint == token_mint.key() @ ErrorCode::InvalidMint,
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    #[account(mut, seeds = [b"stake-pool"], bump = stake_pool.bump)]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake-vault"],
+        bump,
+        token::mint = token_mint,
+    )]
+    pub stake_vault: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake", owner.key().as_ref()],
+        bump = stake_account.bump,
+        has_one = owner @ ErrorCode::Unauthorized,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimStakingRewards<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(mut)]
+    pub owner: Signer<'info>,
+    
+    #[account(seeds = [b"stake-pool"], bump = stake_pool.bump)]
+    pub stake_pool: Account<'info, StakePool>,
+    
+    #[account(
+        mut,
+        seeds = [b"stake", owner.key().as_ref()],
+        bump = stake_account.bump,
+        has_one = owner @ ErrorCode::Unauthorized,
+    )]
+    pub stake_account: Account<'info, StakeAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = owner,
+        associated_token::mint = token_mint,
+        associated_token::authority = owner
+    )]
+    pub owner_token_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Program vault holding the stakers' SOL rewards
     #[account(mut, seeds = [b"vault"], bump)]
     pub program_vault: AccountInfo<'info>,
     
-    /// CHECK: Slot hashes for randomness
-    #[account(address = anchor_lang::solana_program::sysvar::slot_hashes::ID)]
-    pub slot_hashes: AccountInfo<'info>,
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeVesting<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VestingPool::INIT_SPACE,
+        seeds = [b"vesting-pool"],
+        bump
+    )]
+    pub vesting_pool: Account<'info, VestingPool>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(beneficiary: Pubkey, id: u64)]
+pub struct CreateVesting<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub program_state: Account<'info, ProgramState>,
+    
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(mut, seeds = [b"vesting-pool"], bump = vesting_pool.bump)]
+    pub vesting_pool: Account<'info, VestingPool>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + VestingSchedule::INIT_SPACE,
+        seeds = [b"vesting", beneficiary.as_ref(), &id.to_le_bytes()],
+        bump
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
     
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct SellTokens<'info> {
-    #[account(mut)]
+pub struct ClaimVested<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = token_mint @ ErrorCode::InvalidMint,
+    )]
     pub program_state: Account<'info, ProgramState>,
     
     #[account(mut)]
     pub token_mint: Account<'info, Mint>,
     
     #[account(mut)]
-    pub seller: Signer<'info>,
+    pub beneficiary: Signer<'info>,
     
-    #[account(mut)]
-    pub seller_token_account: Account<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [b"vesting", beneficiary.key().as_ref(), &vesting_schedule.id.to_le_bytes()],
+        bump = vesting_schedule.bump,
+        has_one = beneficiary @ ErrorCode::Unauthorized,
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
     
-    /// CHECK: Program vault
-    #[account(mut, seeds = [b"vault"], bump)]
-    pub program_vault: AccountInfo<'info>,
+    #[account(
+        init_if_needed,
+        payer = beneficiary,
+        associated_token::mint = token_mint,
+        associated_token::authority = beneficiary
+    )]
+    pub beneficiary_token_account: Account<'info, TokenAccount>,
     
     pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct Airdrop<'info> {
-    #[account(mut)]
+pub struct RevokeVesting<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
     pub program_state: Account<'info, ProgramState>,
     
-    #[account(mut)]
-    pub token_mint: Account<'info, Mint>,
+    pub authority: Signer<'info>,
+    
+    #[account(mut, seeds = [b"vesting-pool"], bump = vesting_pool.bump)]
+    pub vesting_pool: Account<'info, VestingPool>,
+    
+    #[account(
+        mut,
+        seeds = [b"vesting", vesting_schedule.beneficiary.as_ref(), &vesting_schedule.id.to_le_bytes()],
+        bump = vesting_schedule.bump,
+    )]
+    pub vesting_schedule: Account<'info, VestingSchedule>,
+}
+
+#[derive(Accounts)]
+pub struct ConfigureFeeSplitter<'info> {
+    #[account(
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
+    pub program_state: Account<'info, ProgramState>,
     
     #[account(mut)]
     pub authority: Signer<'info>,
     
-    #[account(mut)]
-    pub recipient_token_account: Account<'info, TokenAccount>,
-
     #[account(
         init_if_needed,
         payer = authority,
-        space = 8 + LastAirdrop::INIT_SPACE,
-        seeds = [b"last-airdrop"],
+        space = 8 + FeeSplitter::INIT_SPACE,
+        seeds = [b"fee-splitter"],
         bump
     )]
-    pub last_airdrop: Account<'info, LastAirdrop>,
+    pub fee_splitter: Account<'info, FeeSplitter>,
     
-    pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
-pub struct ResetAirdrop<'info> {
-    #[account(mut)]
+pub struct DistributeFees<'info> {
+    #[account(mut, seeds = [b"program-state"], bump = program_state.bump)]
     pub program_state: Account<'info, ProgramState>,
     
-    pub authority: Signer<'info>,
+    /// CHECK: Program vault holding the accrued dev fees
+    #[account(mut, seeds = [b"vault"], bump)]
+    pub program_vault: AccountInfo<'info>,
+    
+    #[account(mut, seeds = [b"fee-splitter"], bump = fee_splitter.bump)]
+    pub fee_splitter: Account<'info, FeeSplitter>,
 }
 
 #[derive(Accounts)]
-pub struct UpdateSettings<'info> {
-    #[account(mut)]
+pub struct ResumeTrading<'info> {
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = guardian @ ErrorCode::Unauthorized,
+    )]
     pub program_state: Account<'info, ProgramState>,
     
-    pub authority: Signer<'info>,
+    pub guardian: Signer<'info>,
 }
 
 #[derive(Accounts)]
 pub struct UpdateTokenMint<'info> {
-    #[account(mut)]
+    #[account(
+        mut,
+        seeds = [b"program-state"],
+        bump = program_state.bump,
+        has_one = authority @ ErrorCode::Unauthorized,
+    )]
     pub program_state: Account<'info, ProgramState>,
     
     pub authority: Signer<'info>,
     
+    /// The program must be able to mint the new token
+    #[account(
+        constraint = new_token_mint.mint_authority == COption::Some(program_state.key()) @ ErrorCode::InvalidMint,
+    )]
     pub new_token_mint: Account<'info, Mint>,
 }
 
@@ -594,22 +2987,128 @@ pub struct UpdateTokenMint<'info> {
 
 /// Airdrop every 5 minutes = 300 seconds (simplified from 4:20)
 pub const AIRDROP_INTERVAL: i64 = 300;
-/// Minimum tokens to be eligible for airdrop (level 2+ roughly)
-pub const MIN_AIRDROP_ELIGIBLE: u64 = 10_000_000_000_000; // 10,000 tokens
+/// Minimum whole tokens to be eligible for airdrop (level 2+ roughly), scaled by mint decimals
+pub const MIN_AIRDROP_ELIGIBLE: u64 = 10_000;
 
 #[account]
 #[derive(InitSpace)]
 pub struct ProgramState {
     pub authority: Pubkey,
     pub token_mint: Pubkey,
-    pub current_price: u64,
-    pub total_burned: u64,
+    pub current_price: u128,       // Lamports per token * PRICE_SCALE
+    pub total_burned: u64,         // Fee burns and buybacks; see `supply` for the full split
     pub total_buys: u64,
     pub next_airdrop_time: i64,    // When next airdrop can happen
     pub airdrop_amount: u64,       // Amount per eligible user
     pub last_airdrop_cycle: i64,   // Timestamp of last airdrop cycle
     pub airdrop_executed: bool,    // Whether airdrop was executed this cycle
     pub bump: u8,
+    pub sol_reserve: u64,          // Lamports the curve owes holders (excludes vault rent)
+    pub curve: CurveKind,          // Bonding curve shape, fixed at initialization
+    pub curve_supply: u64,         // Tokens issued by the curve net of tokens sold back
+    pub guardian: Pubkey,          // May resume trading after the circuit breaker trips
+    pub circuit_breaker: CircuitBreaker,
+    pub sell_limits: SellLimits,
+    pub launch_time: i64,          // When the program was initialized; starts the launch phase
+    pub buy_limits: BuyLimits,
+    pub referral_share_bps: u32,   // Share of the raffle fee credited to referrers (of BASIS_POINTS)
+    pub dev_fees_accrued: u64,     // Dev fee lamports in the vault awaiting distribution
+    pub buyback: Buyback,
+    pub total_bought_back: u64,    // Tokens burned by buybacks, also counted in total_burned
+    pub supply: SupplyLedger,      // Reconciles with the mint supply, unlike total_burned
+    pub staking_sol_owed: u64,     // Stakers' dev fee share in the vault awaiting claims
+}
+
+impl ProgramState {
+    /// A program state with no trading history and default settings. The
+    /// caller sets the price and then resets the circuit breaker from it.
+    pub fn launch(
+        authority: Pubkey,
+        token_mint: Pubkey,
+        mint_supply: u64,
+        bump: u8,
+        airdrop_amount: u64,
+        curve: CurveKind,
+        now: i64,
+    ) -> Result<Self> {
+        curve.validate()?;
+        Ok(Self {
+            authority,
+            token_mint,
+            current_price: 0,
+            total_burned: 0,
+            total_buys: 0,
+            next_airdrop_time: calculate_next_airdrop_time(now),
+            airdrop_amount,
+            last_airdrop_cycle: now,
+            airdrop_executed: false,
+            bump,
+            sol_reserve: 0,
+            curve,
+            curve_supply: 0,
+            guardian: authority,
+            circuit_breaker: CircuitBreaker::new(
+                breaker::DEFAULT_MAX_DROP_BPS,
+                breaker::DEFAULT_BREAKER_WINDOW,
+                breaker::DEFAULT_BREAKER_COOLDOWN,
+            )?,
+            sell_limits: SellLimits::new(0, 0, limits::DEFAULT_LIMIT_WINDOW)?,
+            launch_time: now,
+            buy_limits: BuyLimits { window: limits::DEFAULT_LIMIT_WINDOW, ..BuyLimits::default() },
+            referral_share_bps: DEFAULT_REFERRAL_SHARE_BPS,
+            dev_fees_accrued: 0,
+            buyback: Buyback::new(0, 0, buyback::DEFAULT_BUYBACK_INTERVAL, 0)?,
+            total_bought_back: 0,
+            supply: SupplyLedger::new(mint_supply),
+            staking_sol_owed: 0,
+        })
+    }
+
+    pub fn curve_state(&self, decimals: u8) -> CurveState {
+        CurveState {
+            price: self.current_price,
+            sol_reserve: self.sol_reserve,
+            supply: self.curve_supply,
+            decimals,
+        }
+    }
+
+    /// Feed the current price to the circuit breaker; returns the reference high if it trips
+    pub fn observe_price(&mut self, price_before: u128, now: i64) -> Result<Option<u128>> {
+        self.circuit_breaker.observe(price_before, self.current_price, now)
+    }
+
+    /// Lift any halt and measure further drops from the current price
+    pub fn reset_circuit_breaker(&mut self, now: i64) {
+        self.circuit_breaker.resume(self.current_price, now);
+    }
+}
+
+/// Program state as the original program laid it out, before `current_price`
+/// was widened and everything after `bump` was added
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub struct LegacyProgramState {
+    pub authority: Pubkey,
+    pub token_mint: Pubkey,
+    pub current_price: u64,        // Lamports per token, unscaled
+    pub total_burned: u64,
+    pub total_buys: u64,
+    pub next_airdrop_time: i64,
+    pub airdrop_amount: u64,
+    pub last_airdrop_cycle: i64,
+    pub airdrop_executed: bool,
+    pub bump: u8,
+}
+
+impl LegacyProgramState {
+    /// Decode a program state account, failing unless it is in the legacy layout
+    pub fn read(data: &[u8]) -> Result<Self> {
+        require!(
+            data.len() == 8 + Self::INIT_SPACE && data[..8] == <ProgramState as anchor_lang::Discriminator>::DISCRIMINATOR,
+            ErrorCode::NotLegacyProgramState
+        );
+        Ok(Self::deserialize(&mut &data[8..])?)
+    }
 }
 
 #[account]
@@ -623,6 +3122,267 @@ pub struct PurchaseOrder {
     pub bump: u8,
 }
 
+/// SOL escrowed for a buy at or below a target price (PDA seeded by owner and id)
+#[account]
+#[derive(InitSpace)]
+pub struct LimitBuyOrder {
+    pub owner: Pubkey,
+    pub id: u64,                   // Distinguishes orders from the same owner
+    pub sol_amount: u64,           // Lamports spent when the order fills
+    pub target_price: u128,        // Fills once current_price is at or below this
+    pub expiry: i64,
+    pub crank_fee: u64,            // Crank reward plus purchase order rent, also escrowed
+    pub bump: u8,
+}
+
+/// Recurring buys funded up front (PDA seeded by owner and id)
+#[account]
+#[derive(InitSpace)]
+pub struct DcaSchedule {
+    pub owner: Pubkey,
+    pub id: u64,                   // Distinguishes schedules from the same owner
+    pub amount_per_interval: u64,  // Lamports spent per instalment
+    pub interval: i64,             // Seconds between instalments
+    pub intervals_remaining: u32,
+    pub next_run: i64,             // Earliest time the next instalment can execute
+    pub crank_fee: u64,            // Crank reward plus purchase order rent, per instalment
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq, InitSpace)]
+pub enum TriggerDirection {
+    StopLoss,                      // Sell once the price falls to the trigger or below
+    TakeProfit,                    // Sell once the price rises to the trigger or above
+}
+
+impl TriggerDirection {
+    pub fn is_triggered(self, price: u128, trigger_price: u128) -> bool {
+        match self {
+            TriggerDirection::StopLoss => price <= trigger_price,
+            TriggerDirection::TakeProfit => price >= trigger_price,
+        }
+    }
+}
+
+/// Tokens escrowed for a sell once the price crosses a trigger (PDA seeded by owner and id)
+#[account]
+#[derive(InitSpace)]
+pub struct SellOrder {
+    pub owner: Pubkey,
+    pub id: u64,                   // Distinguishes orders from the same owner
+    pub token_amount: u64,         // Held in the order's escrow token account
+    pub trigger_price: u128,       // Lamports per token * PRICE_SCALE
+    pub direction: TriggerDirection,
+    pub min_sol_out: u64,          // Least the owner accepts after the crank reward
+    pub expiry: i64,
+    pub bump: u8,
+}
+
+/// Per-wallet trading history (PDA seeded by the wallet)
+#[account]
+#[derive(InitSpace)]
+pub struct BuyerProfile {
+    pub wallet: Pubkey,
+    pub last_buy_slot: u64,        // Slot of the wallet's most recent buy, 0 if none
+    pub bump: u8,
+    pub market_maker: bool,        // Exempt from sell limits
+    pub sell_window: WindowUsage,  // Tokens sold, decaying over the sell-limit window
+    pub buy_window: WindowUsage,   // Lamports spent, decaying over the buy-limit window
+    pub hold_start: i64,           // Token-weighted time the wallet's tokens were bought, 0 if never
+    pub referrer: Pubkey,          // Bound at the first buy, default if none
+    pub hold_balance: u64,         // Tokens `hold_start` covers; any excess counts as just acquired
+}
+
+/// A beneficiary's consent to buys paid for by `payer` (PDA seeded by both)
+#[account]
+#[derive(InitSpace)]
+pub struct GiftAllowance {
+    pub beneficiary: Pubkey,
+    pub payer: Pubkey,
+    pub bump: u8,
+}
+
+/// Referral earnings and totals for one referrer (PDA seeded by the referrer)
+#[account]
+#[derive(InitSpace)]
+pub struct ReferrerRewards {
+    pub referrer: Pubkey,
+    pub pending: u64,              // Tokens credited but not yet claimed
+    pub total_earned: u64,
+    pub total_claimed: u64,
+    pub referral_count: u32,       // Wallets that bound this referrer
+    pub referred_sol: u64,         // Lamports spent by referred wallets
+    pub bump: u8,
+}
+
+/// Lifetime trade statistics for dashboards (singleton PDA)
+#[account]
+#[derive(InitSpace)]
+pub struct ProtocolStats {
+    pub sol_in: u64,               // Lamports paid in by buys, fees included
+    pub sol_out: u64,              // Lamports paid out by sells
+    pub buy_count: u64,
+    pub sell_count: u64,
+    pub unique_buyers: u64,        // Wallets counted at their first buy
+    pub all_time_high: u128,       // Lamports per token * PRICE_SCALE
+    pub all_time_low: u128,
+    pub total_airdropped: u64,
+    pub bump: u8,
+}
+
+impl ProtocolStats {
+    pub fn observe_price(&mut self, price: u128) {
+        self.all_time_high = self.all_time_high.max(price);
+        self.all_time_low = self.all_time_low.min(price);
+    }
+
+    pub fn record_buy(&mut self, sol_amount: u64, first_buy: bool, price: u128) -> Result<()> {
+        self.sol_in = self.sol_in.checked_add(sol_amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        self.buy_count = self.buy_count.checked_add(1).ok_or(ErrorCode::ArithmeticOverflow)?;
+        if first_buy {
+            self.unique_buyers = self.unique_buyers.checked_add(1).ok_or(ErrorCode::ArithmeticOverflow)?;
+        }
+        self.observe_price(price);
+        Ok(())
+    }
+
+    pub fn record_sell(&mut self, sol_amount: u64, price: u128) -> Result<()> {
+        self.sol_out = self.sol_out.checked_add(sol_amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        self.sell_count = self.sell_count.checked_add(1).ok_or(ErrorCode::ArithmeticOverflow)?;
+        self.observe_price(price);
+        Ok(())
+    }
+
+    pub fn record_airdrop(&mut self, amount: u64) -> Result<()> {
+        self.total_airdropped = self.total_airdropped.checked_add(amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        Ok(())
+    }
+}
+
+/// Recent price observations for TWAP queries (singleton PDA)
+#[account]
+#[derive(InitSpace)]
+pub struct PriceOracle {
+    pub ring: ObservationRing,
+    pub bump: u8,
+}
+
+/// Staking pool totals and the reward-per-token accumulator (singleton PDA)
+#[account]
+#[derive(InitSpace)]
+pub struct StakePool {
+    pub fee_share_bps: u32,        // Share of the dev fee and the sell fee paid to stakers (of BASIS_POINTS)
+    pub unstake_cooldown: i64,     // Seconds after the last stake before unstaking
+    pub total_staked: u64,
+    pub token_rewards: RewardAccumulator, // Sell fee share, in tokens minted on claim
+    pub sol_rewards: RewardAccumulator,   // Dev fee share, in lamports paid from the vault on claim
+    pub bump: u8,
+}
+
+impl StakePool {
+    fn share(&self, fee: u64) -> Result<u64> {
+        math::mul_div(fee, self.fee_share_bps as u64, BASIS_POINTS as u64, math::Rounding::Down)
+    }
+
+    /// Credit stakers their share of a sell fee; returns the tokens taken for them
+    pub fn credit_sell_fee(&mut self, sell_fee: u64) -> Result<u64> {
+        let reward = self.share(sell_fee)?;
+        self.token_rewards.credit(reward, self.total_staked)?;
+        Ok(reward)
+    }
+
+    /// Credit stakers their share of a dev fee; returns the lamports taken for them
+    pub fn credit_dev_fee(&mut self, dev_fee: u64) -> Result<u64> {
+        let reward = self.share(dev_fee)?;
+        self.sol_rewards.credit(reward, self.total_staked)?;
+        Ok(reward)
+    }
+
+    /// Spread queued rewards over the current stakers, if there are any
+    pub fn distribute_queued(&mut self) -> Result<()> {
+        self.token_rewards.distribute_queued(self.total_staked)?;
+        self.sol_rewards.distribute_queued(self.total_staked)
+    }
+}
+
+/// One wallet's stake (PDA seeded by the owner)
+#[account]
+#[derive(InitSpace)]
+pub struct StakeAccount {
+    pub owner: Pubkey,
+    pub amount: u64,
+    pub reward_per_token_paid: u128, // Pool token accumulator at the last settlement
+    pub pending: u64,              // Settled token rewards not yet claimed
+    pub last_staked: i64,
+    pub bump: u8,
+    pub sol_reward_per_token_paid: u128, // Pool SOL accumulator at the last settlement
+    pub pending_sol: u64,          // Settled lamports not yet claimed
+}
+
+impl StakeAccount {
+    /// Move rewards earned since the last settlement into `pending` and `pending_sol`
+    pub fn settle(&mut self, pool: &StakePool) -> Result<()> {
+        let tokens = staking::earned(self.amount, pool.token_rewards.reward_per_token, self.reward_per_token_paid)?;
+        self.pending = self.pending.checked_add(tokens).ok_or(ErrorCode::ArithmeticOverflow)?;
+        self.reward_per_token_paid = pool.token_rewards.reward_per_token;
+        
+        let lamports = staking::earned(self.amount, pool.sol_rewards.reward_per_token, self.sol_reward_per_token_paid)?;
+        self.pending_sol = self.pending_sol.checked_add(lamports).ok_or(ErrorCode::ArithmeticOverflow)?;
+        self.sol_reward_per_token_paid = pool.sol_rewards.reward_per_token;
+        Ok(())
+    }
+}
+
+/// Fixed token allocation that all vesting schedules draw on
+#[account]
+#[derive(InitSpace)]
+pub struct VestingPool {
+    pub allocation: u64,           // Most tokens all live schedules may vest, set once
+    pub total_allocated: u64,      // Tokens committed to schedules, less revoked amounts
+    pub bump: u8,
+}
+
+/// Token allocation vesting to one beneficiary (PDA seeded by beneficiary and id)
+#[account]
+#[derive(InitSpace)]
+pub struct VestingSchedule {
+    pub beneficiary: Pubkey,
+    pub id: u64,                   // Distinguishes schedules for the same beneficiary
+    pub total: u64,                // Reduced to the vested amount on revocation
+    pub claimed: u64,
+    pub start: i64,
+    pub cliff: i64,                // Seconds after start before anything can be claimed
+    pub duration: i64,             // Seconds after start when everything has vested
+    pub revocable: bool,
+    pub revoked: bool,
+    pub bump: u8,
+}
+
+impl VestingSchedule {
+    pub fn vested_at(&self, now: i64) -> Result<u64> {
+        if self.revoked {
+            return Ok(self.total);
+        }
+        vesting::vested_amount(self.total, self.start, self.cliff, self.duration, now)
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct FeeRecipient {
+    pub wallet: Pubkey,
+    pub share_bps: u32,            // Of BASIS_POINTS; all shares sum to BASIS_POINTS
+}
+
+/// Who receives the dev fees and in what shares (singleton PDA)
+#[account]
+#[derive(InitSpace)]
+pub struct FeeSplitter {
+    #[max_len(MAX_FEE_RECIPIENTS)]
+    pub recipients: Vec<FeeRecipient>,
+    pub total_distributed: u64,
+    pub bump: u8,
+}
+
 /// Stores the most recent airdrop winner (singleton PDA).
 /// This lets clients show "who won" without scanning transaction logs.
 #[account]
@@ -635,6 +3395,94 @@ pub struct LastAirdrop {
     pub bump: u8,
 }
 
+// ============= EVENTS =============
+
+#[event]
+pub struct TokensBought {
+    pub buyer: Pubkey,
+    pub sol_amount: u64,
+    pub token_amount: u64,
+    pub ask: u128,                 // Price the buy filled at, lamports per token * PRICE_SCALE
+    pub price: u128,               // Price after the buy, lamports per token * PRICE_SCALE
+    pub unlock_time: i64,
+}
+
+#[event]
+pub struct TokensSold {
+    pub seller: Pubkey,
+    pub token_amount: u64,
+    pub sol_amount: u64,
+    pub early_sell_tax: u64,       // Tokens routed to the airdrop pot
+    pub bid: u128,                 // Price the sell filled at, lamports per token * PRICE_SCALE
+    pub price: u128,               // Price after the sell, lamports per token * PRICE_SCALE
+}
+
+#[event]
+pub struct LimitBuyExecuted {
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub sol_amount: u64,
+    pub token_amount: u64,
+    pub cranker: Pubkey,
+}
+
+#[event]
+pub struct DcaInstalmentExecuted {
+    pub schedule: Pubkey,
+    pub owner: Pubkey,
+    pub sol_amount: u64,
+    pub token_amount: u64,
+    pub intervals_remaining: u32,
+}
+
+#[event]
+pub struct SellOrderExecuted {
+    pub order: Pubkey,
+    pub owner: Pubkey,
+    pub token_amount: u64,
+    pub sol_amount: u64,           // Paid to the owner, after the crank reward
+    pub cranker: Pubkey,
+}
+
+#[event]
+pub struct BuybackExecuted {
+    pub sol_amount: u64,
+    pub tokens_burned: u64,
+    pub price: u128,               // Price after the buyback, lamports per token * PRICE_SCALE
+}
+
+#[event]
+pub struct CircuitBreakerTripped {
+    pub reference_price: u128,     // Window high the drop was measured from
+    pub price: u128,
+    pub halted_until: i64,
+}
+
+#[event]
+pub struct TradingResumed {
+    pub guardian: Pubkey,
+    pub price: u128,
+}
+
+#[event]
+pub struct VestingCreated {
+    pub schedule: Pubkey,
+    pub beneficiary: Pubkey,
+    pub total: u64,
+    pub start: i64,
+    pub cliff: i64,
+    pub duration: i64,
+    pub revocable: bool,
+}
+
+#[event]
+pub struct VestingRevoked {
+    pub schedule: Pubkey,
+    pub beneficiary: Pubkey,
+    pub vested: u64,               // Still claimable by the beneficiary
+    pub cancelled: u64,
+}
+
 // ============= ERRORS =============
 
 #[error_code]
@@ -663,4 +3511,196 @@ pub enum ErrorCode {
     AirdropNotReady,
     #[msg("Airdrop already executed this cycle - only one winner per cycle")]
     AirdropAlreadyExecuted,
+    #[msg("Sell exceeds the SOL reserve owed by the curve")]
+    InsufficientReserve,
+    #[msg("Token mint has more decimals than supported")]
+    InvalidMintDecimals,
+    #[msg("Invalid bonding curve parameters")]
+    InvalidCurve,
+    #[msg("Bonding curve has no tokens left to sell")]
+    CurveExhausted,
+    #[msg("Trade crosses too many curve steps - split it into smaller trades")]
+    CurveStepLimit,
+    #[msg("Token account or mint does not match the program's token mint")]
+    InvalidMint,
+    #[msg("A transaction cannot both buy and sell")]
+    BuyAndSellInSameTransaction,
+    #[msg("Trades must be called directly, not through another program")]
+    TradeViaCpi,
+    #[msg("Too soon after this wallet's last buy - wait a few slots before selling")]
+    SellTooSoonAfterBuy,
+    #[msg("Sells are halted by the circuit breaker")]
+    TradingHalted,
+    #[msg("Invalid circuit breaker settings")]
+    InvalidBreakerConfig,
+    #[msg("Sell exceeds this wallet's limit for the current window")]
+    SellLimitExceeded,
+    #[msg("Invalid trading limit settings")]
+    InvalidLimitConfig,
+    #[msg("Buy exceeds this wallet's limit for the current window")]
+    BuyLimitExceeded,
+    #[msg("Buy would put this wallet over the maximum holding")]
+    MaxHoldingExceeded,
+    #[msg("A wallet cannot refer itself")]
+    SelfReferral,
+    #[msg("Referrer must have bought before and match the wallet's bound referrer")]
+    InvalidReferrer,
+    #[msg("Nothing to claim")]
+    NothingToClaim,
+    #[msg("Invalid amount")]
+    InvalidAmount,
+    #[msg("Stake is still in its cooldown")]
+    StakeCooldown,
+    #[msg("Invalid vesting schedule")]
+    InvalidVesting,
+    #[msg("Vesting schedule cannot be revoked")]
+    VestingNotRevocable,
+    #[msg("Fee split must list distinct recipients whose shares sum to 100%")]
+    InvalidFeeSplit,
+    #[msg("Buyback interval has not passed yet")]
+    BuybackTooSoon,
+    #[msg("No surplus SOL to buy back with")]
+    NothingToBuyBack,
+    #[msg("TWAP window must be positive and covered by the price history")]
+    InvalidTwapWindow,
+    #[msg("Order settings are invalid")]
+    InvalidOrder,
+    #[msg("Order has expired")]
+    OrderExpired,
+    #[msg("Price is above the limit order's target")]
+    LimitPriceNotReached,
+    #[msg("Price has not crossed the sell order's trigger")]
+    SellOrderNotTriggered,
+    #[msg("Sale proceeds are below the order's minimum")]
+    SlippageExceeded,
+    #[msg("DCA instalment is not due yet")]
+    DcaNotDue,
+    #[msg("Vesting schedule exceeds the remaining vesting allocation")]
+    VestingAllocationExceeded,
+    #[msg("Program state is not in the legacy layout")]
+    NotLegacyProgramState,
+    #[msg("The beneficiary has not allowed gifts from this buyer")]
+    GiftNotAllowed,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_tiers_switch_exactly_at_thresholds() {
+        let tiers = [
+            (MIN_BUY_AMOUNT, TIER1_MIN, TIER1_MAX),
+            (TIER1_THRESHOLD - 1, TIER1_MIN, TIER1_MAX),
+            (TIER1_THRESHOLD, TIER2_MIN, TIER2_MAX),
+            (TIER2_THRESHOLD - 1, TIER2_MIN, TIER2_MAX),
+            (TIER2_THRESHOLD, TIER3_MIN, TIER3_MAX),
+            (TIER3_THRESHOLD - 1, TIER3_MIN, TIER3_MAX),
+            (TIER3_THRESHOLD, TIER4_MIN, TIER4_MAX),
+            (MAX_BUY_AMOUNT, TIER4_MIN, TIER4_MAX),
+        ];
+        for (sol_amount, min, max) in tiers {
+            for seed in [0u64, 1, u64::MAX] {
+                let lock = calculate_lock_duration(sol_amount, 1_700_000_000, 0, &seed.to_le_bytes()).unwrap();
+                assert!(lock >= min && lock < max, "{} lamports locked for {}s", sol_amount, lock);
+            }
+        }
+    }
+
+    #[test]
+    fn next_airdrop_time_aligns_to_interval() {
+        assert_eq!(calculate_next_airdrop_time(0), AIRDROP_INTERVAL);
+        assert_eq!(calculate_next_airdrop_time(1), AIRDROP_INTERVAL);
+        assert_eq!(calculate_next_airdrop_time(AIRDROP_INTERVAL - 1), AIRDROP_INTERVAL);
+        assert_eq!(calculate_next_airdrop_time(AIRDROP_INTERVAL), 2 * AIRDROP_INTERVAL);
+    }
+
+    #[test]
+    fn stats_count_unique_buyers_and_price_extremes() {
+        let mut stats = ProtocolStats {
+            sol_in: 0,
+            sol_out: 0,
+            buy_count: 0,
+            sell_count: 0,
+            unique_buyers: 0,
+            all_time_high: 1_000,
+            all_time_low: 1_000,
+            total_airdropped: 0,
+            bump: 0,
+        };
+        stats.record_buy(500, true, 1_500).unwrap();
+        stats.record_buy(500, false, 2_000).unwrap();
+        stats.record_sell(300, 800).unwrap();
+
+        assert_eq!((stats.sol_in, stats.sol_out), (1_000, 300));
+        assert_eq!((stats.buy_count, stats.sell_count, stats.unique_buyers), (2, 1, 1));
+        assert_eq!((stats.all_time_high, stats.all_time_low), (2_000, 800));
+    }
+
+    #[test]
+    fn sell_triggers_include_the_trigger_price() {
+        assert!(TriggerDirection::StopLoss.is_triggered(900, 1_000));
+        assert!(TriggerDirection::StopLoss.is_triggered(1_000, 1_000));
+        assert!(!TriggerDirection::StopLoss.is_triggered(1_001, 1_000));
+        assert!(TriggerDirection::TakeProfit.is_triggered(1_000, 1_000));
+        assert!(!TriggerDirection::TakeProfit.is_triggered(999, 1_000));
+    }
+
+    #[test]
+    fn legacy_program_state_is_only_read_in_its_original_layout() {
+        let legacy = LegacyProgramState {
+            authority: Pubkey::new_unique(),
+            token_mint: Pubkey::new_unique(),
+            current_price: 10_000,
+            total_burned: 5,
+            total_buys: 7,
+            next_airdrop_time: 600,
+            airdrop_amount: 1_000,
+            last_airdrop_cycle: 300,
+            airdrop_executed: true,
+            bump: 254,
+        };
+        let mut data = <ProgramState as anchor_lang::Discriminator>::DISCRIMINATOR.to_vec();
+        legacy.serialize(&mut data).unwrap();
+        assert_eq!(data.len(), 8 + LegacyProgramState::INIT_SPACE);
+
+        let read = LegacyProgramState::read(&data).unwrap();
+        assert_eq!((read.authority, read.current_price, read.bump), (legacy.authority, 10_000, 254));
+
+        // Already migrated, or some other account entirely
+        let mut current = data.clone();
+        current.resize(8 + ProgramState::INIT_SPACE, 0);
+        assert!(LegacyProgramState::read(&current).is_err());
+        data[0] ^= 1;
+        assert!(LegacyProgramState::read(&data).is_err());
+        assert!(LegacyProgramState::read(&[]).is_err());
+    }
+
+    #[test]
+    fn launched_state_fits_its_account() {
+        let curve = CurveKind::Linear { increment: 1, decrement: 1 };
+        let state = ProgramState::launch(Pubkey::new_unique(), Pubkey::new_unique(), 1_000, 255, 100, curve, 301).unwrap();
+        assert_eq!(state.try_to_vec().unwrap().len(), ProgramState::INIT_SPACE);
+        assert_eq!(state.next_airdrop_time, 600);
+        assert_eq!(state.guardian, state.authority);
+
+        assert!(ProgramState::launch(Pubkey::new_unique(), Pubkey::new_unique(), 0, 255, 100, CurveKind::Stepped { step_size: 0, step_increment: 1 }, 0).is_err());
+    }
+
+    #[test]
+    fn exact_output_buys_deliver_where_one_lamport_less_does_not() {
+        let curve = CurveKind::Linear { increment: 100_000_000_000, decrement: 100_000_000_000 };
+        // 1,000 lamports per token
+        let curve_state = CurveState { price: 1_000_000_000_000, sol_reserve: 0, supply: 0, decimals: 9 };
+        for token_amount in [1, 12_345_678_901_234, 10_000_000_000_000, 5_000_000_000_000_000] {
+            let sol_amount = sol_for_exact_tokens(&curve, &curve_state, token_amount).unwrap();
+            assert!(buy_output(&curve, &curve_state, sol_amount).unwrap() >= token_amount);
+            if sol_amount > MIN_BUY_AMOUNT {
+                assert!(buy_output(&curve, &curve_state, sol_amount - 1).unwrap() < token_amount);
+            }
+        }
+        // More than the largest buy delivers
+        let too_many = buy_output(&curve, &curve_state, MAX_BUY_AMOUNT).unwrap() + 1;
+        assert!(sol_for_exact_tokens(&curve, &curve_state, too_many).is_err());
+    }
 }
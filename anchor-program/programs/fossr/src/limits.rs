@@ -0,0 +1,242 @@
+//! Per-wallet trading caps.
+//!
+//! Usage is kept on each wallet's profile and decays linearly to zero over the
+//! window, so the cap frees up gradually rather than all at once at a window
+//! boundary. A wallet that used its whole cap can't use it again straight
+//! away, however it times its trades.
+
+use anchor_lang::prelude::*;
+
+use crate::math::{self, Rounding};
+use crate::{ErrorCode, BASIS_POINTS};
+
+/// Caps are counted over 24 hours unless configured otherwise
+pub const DEFAULT_LIMIT_WINDOW: i64 = 24 * 60 * 60;
+
+/// The tighter of two optional caps
+fn tighter(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (cap, None) | (None, cap) => cap,
+    }
+}
+
+/// A cap stored as 0-for-none
+fn cap_of(value: u64) -> Option<u64> {
+    (value > 0).then_some(value)
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct WindowUsage {
+    pub updated_at: i64,
+    pub used: u64,                 // Usage as of updated_at, before decay
+}
+
+impl WindowUsage {
+    /// Usage left at `now` after decaying over `window` seconds
+    pub fn current(&self, window: i64, now: i64) -> Result<u64> {
+        let elapsed = now.saturating_sub(self.updated_at).max(0);
+        if elapsed >= window {
+            return Ok(0);
+        }
+        // Rounded up so the cap never frees up early
+        math::mul_div(self.used, (window - elapsed) as u64, window as u64, Rounding::Up)
+    }
+
+    /// Add `amount` to the decayed usage, failing with `error` if that exceeds `cap`
+    pub fn record(&mut self, amount: u64, cap: Option<u64>, window: i64, now: i64, error: ErrorCode) -> Result<()> {
+        let used = self.current(window, now)?.checked_add(amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        if let Some(cap) = cap {
+            if used > cap {
+                return Err(error.into());
+            }
+        }
+        self.used = used;
+        self.updated_at = now;
+        Ok(())
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct SellLimits {
+    pub max_tokens: u64,           // Atomic units per window, 0 for no token cap
+    pub max_supply_bps: u32,       // Share of mint supply per window (of BASIS_POINTS), 0 for none
+    pub window: i64,               // Seconds
+}
+
+impl SellLimits {
+    pub fn new(max_tokens: u64, max_supply_bps: u32, window: i64) -> Result<Self> {
+        require!(max_supply_bps <= BASIS_POINTS && window > 0, ErrorCode::InvalidLimitConfig);
+        Ok(Self { max_tokens, max_supply_bps, window })
+    }
+
+    /// The tighter of the two caps for a mint with `supply`, or `None` if neither is set
+    pub fn cap(&self, supply: u64) -> Result<Option<u64>> {
+        let by_supply = if self.max_supply_bps > 0 {
+            Some(math::mul_div(supply, self.max_supply_bps as u64, BASIS_POINTS as u64, Rounding::Down)?)
+        } else {
+            None
+        };
+        Ok(tighter(cap_of(self.max_tokens), by_supply))
+    }
+}
+
+/// Buy caps. During the launch phase the launch caps apply on top of the
+/// regular ones, whichever is tighter.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct BuyLimits {
+    pub max_sol: u64,              // Lamports spent per window, 0 for no cap
+    pub max_holding: u64,          // Atomic units a wallet may hold after a buy, 0 for no cap
+    pub window: i64,               // Seconds
+    pub launch_duration: i64,      // Seconds after launch the launch caps apply
+    pub launch_max_sol: u64,
+    pub launch_max_holding: u64,
+}
+
+impl BuyLimits {
+    pub fn validate(&self) -> Result<()> {
+        require!(self.window > 0 && self.launch_duration >= 0, ErrorCode::InvalidLimitConfig);
+        Ok(())
+    }
+
+    pub fn in_launch(&self, launch_time: i64, now: i64) -> bool {
+        now < launch_time.saturating_add(self.launch_duration)
+    }
+
+    /// Lamports a wallet may spend per window at `now`
+    pub fn sol_cap(&self, launch_time: i64, now: i64) -> Option<u64> {
+        let launch = self.in_launch(launch_time, now).then_some(self.launch_max_sol).and_then(cap_of);
+        tighter(cap_of(self.max_sol), launch)
+    }
+
+    /// Tokens a wallet may hold after a buy at `now`
+    pub fn holding_cap(&self, launch_time: i64, now: i64) -> Option<u64> {
+        let launch = self.in_launch(launch_time, now).then_some(self.launch_max_holding).and_then(cap_of);
+        tighter(cap_of(self.max_holding), launch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = DEFAULT_LIMIT_WINDOW;
+
+    #[test]
+    fn sell_cap_takes_the_tighter_limit() {
+        let supply = 1_000_000;
+        assert_eq!(SellLimits::new(0, 0, DAY).unwrap().cap(supply).unwrap(), None);
+        assert_eq!(SellLimits::new(5_000, 0, DAY).unwrap().cap(supply).unwrap(), Some(5_000));
+        // 1% of supply
+        assert_eq!(SellLimits::new(0, 1_000, DAY).unwrap().cap(supply).unwrap(), Some(10_000));
+        assert_eq!(SellLimits::new(50_000, 1_000, DAY).unwrap().cap(supply).unwrap(), Some(10_000));
+        assert_eq!(SellLimits::new(5_000, 1_000, DAY).unwrap().cap(supply).unwrap(), Some(5_000));
+    }
+
+    #[test]
+    fn sell_limits_reject_impossible_settings() {
+        assert!(SellLimits::new(0, BASIS_POINTS + 1, DAY).is_err());
+        assert!(SellLimits::new(0, 0, 0).is_err());
+    }
+
+    fn buy_limits() -> BuyLimits {
+        BuyLimits {
+            max_sol: 10_000,
+            max_holding: 0,
+            window: DAY,
+            launch_duration: 600,
+            launch_max_sol: 1_000,
+            launch_max_holding: 50_000,
+        }
+    }
+
+    #[test]
+    fn buy_caps_are_stricter_during_launch() {
+        let limits = buy_limits();
+        let launch = 1_000;
+        assert_eq!(limits.sol_cap(launch, launch), Some(1_000));
+        assert_eq!(limits.holding_cap(launch, launch + 599), Some(50_000));
+        assert_eq!(limits.sol_cap(launch, launch + 600), Some(10_000));
+        assert_eq!(limits.holding_cap(launch, launch + 600), None);
+    }
+
+    #[test]
+    fn launch_caps_never_loosen_the_regular_ones() {
+        let limits = BuyLimits { launch_max_sol: 1_000_000, max_holding: 20_000, ..buy_limits() };
+        assert_eq!(limits.sol_cap(0, 0), Some(10_000));
+        assert_eq!(limits.holding_cap(0, 0), Some(20_000));
+
+        let unset = BuyLimits { window: DAY, ..BuyLimits::default() };
+        assert_eq!(unset.sol_cap(0, 0), None);
+        assert_eq!(unset.holding_cap(0, 0), None);
+    }
+
+    #[test]
+    fn buy_limits_reject_impossible_settings() {
+        assert!(buy_limits().validate().is_ok());
+        assert!(BuyLimits { window: 0, ..buy_limits() }.validate().is_err());
+        assert!(BuyLimits { launch_duration: -1, ..buy_limits() }.validate().is_err());
+    }
+
+    #[test]
+    fn window_usage_accumulates_up_to_the_cap() {
+        let mut usage = WindowUsage::default();
+        usage.record(600, Some(1_000), DAY, 10, ErrorCode::SellLimitExceeded).unwrap();
+        usage.record(400, Some(1_000), DAY, 20, ErrorCode::SellLimitExceeded).unwrap();
+        assert_eq!(
+            usage.record(1, Some(1_000), DAY, 30, ErrorCode::SellLimitExceeded).unwrap_err(),
+            error!(ErrorCode::SellLimitExceeded)
+        );
+        // A rejected trade doesn't count
+        assert_eq!(usage.current(DAY, 30).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn window_usage_frees_up_linearly() {
+        let mut usage = WindowUsage::default();
+        usage.record(1_000, Some(1_000), DAY, 10, ErrorCode::SellLimitExceeded).unwrap();
+        assert_eq!(usage.current(DAY, 10 + DAY / 4).unwrap(), 750);
+        assert_eq!(usage.current(DAY, 10 + DAY).unwrap(), 0);
+
+        // Half the cap is back halfway through the window
+        usage.record(500, Some(1_000), DAY, 10 + DAY / 2, ErrorCode::SellLimitExceeded).unwrap();
+        assert!(usage.record(1, Some(1_000), DAY, 10 + DAY / 2, ErrorCode::SellLimitExceeded).is_err());
+    }
+
+    #[test]
+    fn sell_cap_cannot_be_doubled_across_a_window_boundary() {
+        let mut usage = WindowUsage::default();
+        // The whole cap just before where a fixed window would end...
+        usage.record(1_000, Some(1_000), DAY, DAY - 1, ErrorCode::SellLimitExceeded).unwrap();
+        // ...leaves almost nothing just after it
+        assert_eq!(
+            usage.record(1_000, Some(1_000), DAY, DAY + 1, ErrorCode::SellLimitExceeded).unwrap_err(),
+            error!(ErrorCode::SellLimitExceeded)
+        );
+        assert!(usage.record(2, Some(1_000), DAY, DAY + 1, ErrorCode::SellLimitExceeded).is_err());
+    }
+
+    #[test]
+    fn buy_cap_cannot_be_doubled_across_a_window_boundary() {
+        let limits = buy_limits();
+        let mut usage = WindowUsage::default();
+        // Past the launch phase the regular 10,000 cap applies
+        usage.record(10_000, limits.sol_cap(0, DAY - 1), DAY, DAY - 1, ErrorCode::BuyLimitExceeded).unwrap();
+        assert_eq!(
+            usage.record(10_000, limits.sol_cap(0, DAY + 1), DAY, DAY + 1, ErrorCode::BuyLimitExceeded).unwrap_err(),
+            error!(ErrorCode::BuyLimitExceeded)
+        );
+
+        // Nor can the launch cap be spent twice during the launch phase
+        let mut usage = WindowUsage::default();
+        usage.record(1_000, limits.sol_cap(0, 0), DAY, 0, ErrorCode::BuyLimitExceeded).unwrap();
+        assert!(usage.record(1_000, limits.sol_cap(0, 599), DAY, 599, ErrorCode::BuyLimitExceeded).is_err());
+    }
+
+    #[test]
+    fn uncapped_usage_is_still_tracked() {
+        let mut usage = WindowUsage::default();
+        usage.record(u64::MAX / 2, None, DAY, 10, ErrorCode::SellLimitExceeded).unwrap();
+        assert_eq!(usage.current(DAY, 10).unwrap(), u64::MAX / 2);
+    }
+}
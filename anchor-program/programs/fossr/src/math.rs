@@ -0,0 +1,438 @@
+//! Price, fee and amount math for the bonding curve.
+//!
+//! Every product is taken in u128 so any u64 amount can be multiplied by any
+//! u64 price or fee without overflowing, and every division says which way it
+//! rounds. Rounding always favours the protocol: amounts paid out are rounded
+//! down and fees are rounded up.
+//!
+//! Prices are fixed-point: lamports per whole token multiplied by `PRICE_SCALE`.
+//! Token amounts are atomic units, so conversions take the mint's decimals.
+
+use anchor_lang::prelude::*;
+
+use crate::{
+    ErrorCode, BASIS_POINTS, BURN_FEE_BPS, EARLY_SELL_TAX_BPS, EARLY_SELL_TAX_PERIOD, MAX_TOKEN_DECIMALS,
+    MIN_PRICE, PRICE_SCALE, RAFFLE_FEE_BPS, SELL_BURN_FEE_BPS,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+}
+
+/// `a * b / denominator` with a u128 intermediate and explicit rounding
+pub fn mul_div(a: u64, b: u64, denominator: u64, rounding: Rounding) -> Result<u64> {
+    to_u64(mul_div_u128(a as u128, b as u128, denominator as u128, rounding)?)
+}
+
+/// `a * b / denominator` in u128, failing if the product itself overflows
+pub fn mul_div_u128(a: u128, b: u128, denominator: u128, rounding: Rounding) -> Result<u128> {
+    require!(denominator > 0, ErrorCode::ArithmeticOverflow);
+    let product = a.checked_mul(b).ok_or(ErrorCode::ArithmeticOverflow)?;
+    Ok(match rounding {
+        Rounding::Down => product / denominator,
+        Rounding::Up => product.div_ceil(denominator),
+    })
+}
+
+/// Fee of `fee_bps` (out of `BASIS_POINTS`) on `amount`
+pub fn fee(amount: u64, fee_bps: u16, rounding: Rounding) -> Result<u64> {
+    mul_div(amount, fee_bps as u64, BASIS_POINTS as u64, rounding)
+}
+
+pub fn to_u64(value: u128) -> Result<u64> {
+    Ok(u64::try_from(value).map_err(|_| ErrorCode::ArithmeticOverflow)?)
+}
+
+/// Atomic units per whole token for a mint with `decimals`
+pub fn token_unit(decimals: u8) -> Result<u128> {
+    require!(decimals <= MAX_TOKEN_DECIMALS, ErrorCode::InvalidMintDecimals);
+    Ok(10u128.pow(decimals as u32))
+}
+
+/// `whole_tokens` expressed in atomic units of a mint with `decimals`
+pub fn whole_tokens(whole_tokens: u64, decimals: u8) -> Result<u64> {
+    to_u64((whole_tokens as u128).checked_mul(token_unit(decimals)?).ok_or(ErrorCode::ArithmeticOverflow)?)
+}
+
+/// Tokens (atomic units) that `sol_amount` lamports buy at the fixed-point `price`
+pub fn tokens_for_sol(sol_amount: u64, price: u128, decimals: u8, rounding: Rounding) -> Result<u64> {
+    require!(price > 0, ErrorCode::InvalidPrice);
+    let sol_in_atoms = (sol_amount as u128) * token_unit(decimals)?;
+    to_u64(mul_div_u128(sol_in_atoms, PRICE_SCALE, price, rounding)?)
+}
+
+/// Lamports that `token_amount` atomic units are worth at the fixed-point `price`
+pub fn sol_for_tokens(token_amount: u64, price: u128, decimals: u8, rounding: Rounding) -> Result<u64> {
+    require!(price > 0, ErrorCode::InvalidPrice);
+    let atoms_per_scaled_token = token_unit(decimals)? * PRICE_SCALE;
+    to_u64(mul_div_u128(token_amount as u128, price, atoms_per_scaled_token, rounding)?)
+}
+
+/// Fixed-point price at which `sol_amount` lamports traded for `token_amount`
+/// atomic units; zero for an empty fill
+pub fn average_price(sol_amount: u64, token_amount: u64, decimals: u8, rounding: Rounding) -> Result<u128> {
+    if token_amount == 0 {
+        return Ok(0);
+    }
+    let sol_in_atoms = (sol_amount as u128) * token_unit(decimals)?;
+    mul_div_u128(sol_in_atoms, PRICE_SCALE, token_amount as u128, rounding)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BuyAmounts {
+    pub tokens_before_fees: u64,
+    pub burn_amount: u64,
+    pub raffle_fee_amount: u64,
+    pub tokens_to_buyer: u64,
+}
+
+/// Split the `tokens_before_fees` a buy filled on the curve into fees and the buyer's share
+pub fn split_buy(tokens_before_fees: u64) -> Result<BuyAmounts> {
+    let burn_amount = fee(tokens_before_fees, BURN_FEE_BPS, Rounding::Up)?;
+    let raffle_fee_amount = fee(tokens_before_fees, RAFFLE_FEE_BPS, Rounding::Up)?;
+
+    // Rounded-up fees can swallow a dust-sized fill entirely
+    let tokens_to_buyer = tokens_before_fees
+        .checked_sub(burn_amount)
+        .and_then(|tokens| tokens.checked_sub(raffle_fee_amount))
+        .filter(|tokens| *tokens > 0)
+        .ok_or(ErrorCode::BuyAmountTooSmall)?;
+
+    Ok(BuyAmounts {
+        tokens_before_fees,
+        burn_amount,
+        raffle_fee_amount,
+        tokens_to_buyer,
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SellAmounts {
+    pub sell_burn: u64,
+    pub early_sell_tax: u64,
+    pub tokens_to_convert: u64,
+}
+
+/// Split a sell of `token_amount` atomic units into the burn fee, the early-sell
+/// tax of `early_sell_tax_bps` and the tokens the curve buys back
+pub fn split_sell(token_amount: u64, early_sell_tax_bps: u16) -> Result<SellAmounts> {
+    let sell_burn = fee(token_amount, SELL_BURN_FEE_BPS, Rounding::Up)?;
+    let early_sell_tax = fee(token_amount, early_sell_tax_bps, Rounding::Up)?;
+    let tokens_to_convert = token_amount
+        .checked_sub(sell_burn)
+        .and_then(|tokens| tokens.checked_sub(early_sell_tax))
+        .ok_or(ErrorCode::SellAmountTooSmall)?;
+
+    Ok(SellAmounts {
+        sell_burn,
+        early_sell_tax,
+        tokens_to_convert,
+    })
+}
+
+/// Early-sell tax for tokens held for `held_for` seconds: `EARLY_SELL_TAX_BPS`
+/// straight after buying, decaying linearly to zero at `EARLY_SELL_TAX_PERIOD`
+pub fn early_sell_tax_bps(held_for: i64) -> u16 {
+    if held_for >= EARLY_SELL_TAX_PERIOD {
+        return 0;
+    }
+    let remaining = (EARLY_SELL_TAX_PERIOD - held_for.max(0)) as u64;
+    // Rounded up so the tax only reaches zero once the period is over
+    (EARLY_SELL_TAX_BPS as u64 * remaining).div_ceil(EARLY_SELL_TAX_PERIOD as u64) as u16
+}
+
+/// Seconds a wallet has held its tokens. A wallet with no recorded buy got
+/// its tokens some other way, such as a transfer, and counts as having just
+/// bought so moving tokens to a fresh wallet doesn't skip the early-sell tax.
+pub fn held_for(hold_start: i64, now: i64) -> i64 {
+    if hold_start == 0 {
+        0
+    } else {
+        now.saturating_sub(hold_start)
+    }
+}
+
+/// Token-weighted acquisition time after `bought` tokens join a `balance`
+/// acquired on average at `hold_start`. A balance with no recorded buy counts
+/// as acquired at `now`, like in `held_for`.
+pub fn blended_hold_start(balance: u64, hold_start: i64, bought: u64, now: i64) -> Result<i64> {
+    let hold_start = if hold_start == 0 { now } else { hold_start };
+    let total = (balance as i128) + (bought as i128);
+    if total == 0 {
+        return Ok(now);
+    }
+    let weighted = (balance as i128) * (hold_start as i128) + (bought as i128) * (now as i128);
+    // Rounded up (later) so splitting a buy never makes tokens look older
+    let blended = weighted.div_euclid(total) + i128::from(weighted.rem_euclid(total) != 0);
+    i64::try_from(blended).map_err(|_| error!(ErrorCode::ArithmeticOverflow))
+}
+
+/// Acquisition time of a `balance` of which only `hold_balance` tokens are
+/// known to date from `hold_start`. Anything beyond that arrived some other
+/// way, such as a transfer from an older wallet, and counts as acquired at
+/// `now`, so aged wallets can't lend their age to newly bought tokens.
+pub fn effective_hold_start(balance: u64, hold_balance: u64, hold_start: i64, now: i64) -> Result<i64> {
+    let recorded = balance.min(hold_balance);
+    blended_hold_start(recorded, hold_start, balance - recorded, now)
+}
+
+/// `MIN_PRICE` in fixed-point
+pub fn min_price() -> u128 {
+    (MIN_PRICE as u128) * PRICE_SCALE
+}
+
+/// Fixed-point price for `lamports` per whole token
+pub fn price_from_lamports(lamports: u64) -> u128 {
+    (lamports as u128) * PRICE_SCALE
+}
+
+/// Share of the SOL reserve backing `tokens` out of the current mint `supply`
+pub fn reserve_share(sol_reserve: u64, tokens: u64, supply: u64) -> Result<u64> {
+    require!(supply > 0, ErrorCode::InsufficientReserve);
+    mul_div(sol_reserve, tokens, supply, Rounding::Down)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::native_token::LAMPORTS_PER_SOL;
+    use crate::{MAX_BUY_AMOUNT, MIN_AIRDROP_ELIGIBLE, MIN_BUY_AMOUNT, MIN_PRICE, MIN_SELL_AMOUNT};
+
+    const TOKEN_PRECISION: u64 = 1_000_000_000;
+
+    /// Largest buy the old u64 `sol_amount * 1e9` could handle
+    const OLD_U64_LIMIT: u64 = u64::MAX / TOKEN_PRECISION;
+
+    fn lamports(price: u64) -> u128 {
+        price_from_lamports(price)
+    }
+
+    /// Fee split of a buy filled at the spot price
+    fn buy_amounts(sol_amount: u64, price: u128, decimals: u8) -> Result<BuyAmounts> {
+        split_buy(tokens_for_sol(sol_amount, price, decimals, Rounding::Down)?)
+    }
+
+    /// Fee split of a sell filled at the spot price, with the lamports it pays
+    fn sell_amounts(token_amount: u64, price: u128, decimals: u8) -> Result<(SellAmounts, u64)> {
+        let amounts = split_sell(token_amount, 0)?;
+        Ok((amounts, sol_for_tokens(amounts.tokens_to_convert, price, decimals, Rounding::Down)?))
+    }
+
+    #[test]
+    fn mul_div_rounds_in_the_requested_direction() {
+        assert_eq!(mul_div(10, 1, 3, Rounding::Down).unwrap(), 3);
+        assert_eq!(mul_div(10, 1, 3, Rounding::Up).unwrap(), 4);
+        assert_eq!(mul_div(9, 1, 3, Rounding::Up).unwrap(), 3);
+        assert_eq!(mul_div(0, 7, 3, Rounding::Up).unwrap(), 0);
+    }
+
+    #[test]
+    fn mul_div_keeps_full_u64_range_through_u128() {
+        assert_eq!(mul_div(u64::MAX, u64::MAX, u64::MAX, Rounding::Down).unwrap(), u64::MAX);
+        assert_eq!(mul_div(u64::MAX, 2, 4, Rounding::Up).unwrap(), u64::MAX / 2 + 1);
+        assert!(mul_div(u64::MAX, 2, 1, Rounding::Down).is_err());
+        assert!(mul_div(1, 1, 0, Rounding::Down).is_err());
+    }
+
+    #[test]
+    fn fees_round_up_and_are_bounded_by_the_amount() {
+        assert_eq!(fee(1, BURN_FEE_BPS, Rounding::Up).unwrap(), 1);
+        assert_eq!(fee(1, BURN_FEE_BPS, Rounding::Down).unwrap(), 0);
+        assert_eq!(fee(BASIS_POINTS as u64, RAFFLE_FEE_BPS, Rounding::Up).unwrap(), RAFFLE_FEE_BPS as u64);
+        assert_eq!(fee(u64::MAX, u16::MAX, Rounding::Down).unwrap(), (u64::MAX as u128 * u16::MAX as u128 / BASIS_POINTS as u128) as u64);
+    }
+
+    #[test]
+    fn buys_past_the_old_u64_limit_no_longer_overflow() {
+        assert!(OLD_U64_LIMIT.checked_add(1).unwrap().checked_mul(TOKEN_PRECISION).is_none());
+
+        for sol_amount in [OLD_U64_LIMIT, OLD_U64_LIMIT + 1, 18_500_000_000, 50_000_000_000] {
+            let amounts = buy_amounts(sol_amount, min_price(), 9).unwrap();
+            assert_eq!(amounts.tokens_before_fees as u128, sol_amount as u128 * TOKEN_PRECISION as u128 / MIN_PRICE as u128);
+        }
+    }
+
+    #[test]
+    fn buy_amount_bounds() {
+        // Smallest and largest allowed buys at the lowest allowed price
+        for sol_amount in [MIN_BUY_AMOUNT, MAX_BUY_AMOUNT] {
+            let amounts = buy_amounts(sol_amount, min_price(), 9).unwrap();
+            assert_eq!(
+                amounts.tokens_to_buyer + amounts.burn_amount + amounts.raffle_fee_amount,
+                amounts.tokens_before_fees
+            );
+        }
+
+        // The largest buy at the lowest price is still inside u64
+        let amounts = buy_amounts(MAX_BUY_AMOUNT, min_price(), 9).unwrap();
+        assert_eq!(amounts.tokens_before_fees, MAX_BUY_AMOUNT / MIN_PRICE * TOKEN_PRECISION);
+
+        // ...but a price of 1 lamport per token would mint more than u64 can hold
+        assert!(buy_amounts(MAX_BUY_AMOUNT, lamports(1), 9).is_err());
+        assert!(buy_amounts(MIN_BUY_AMOUNT, 0, 9).is_err());
+    }
+
+    #[test]
+    fn buy_fees_round_against_the_buyer() {
+        // 1 SOL at 1 SOL per token: 1e9 atoms, fees land on whole atoms
+        let amounts = buy_amounts(1_000_000_000, lamports(1_000_000_000), 9).unwrap();
+        assert_eq!(amounts.burn_amount, 690_000);
+        assert_eq!(amounts.raffle_fee_amount, 6_210_000);
+
+        // One atom more pushes both fees up rather than down
+        let amounts = buy_amounts(1_000_000_001, lamports(1_000_000_000), 9).unwrap();
+        assert_eq!(amounts.burn_amount, 690_001);
+        assert_eq!(amounts.raffle_fee_amount, 6_210_001);
+    }
+
+    #[test]
+    fn one_more_atom_of_fill_can_deliver_one_less() {
+        // Both rounded-up fees tick over at once
+        assert_eq!(split_buy(1_449).unwrap().tokens_to_buyer, 1_439);
+        assert_eq!(split_buy(1_450).unwrap().tokens_to_buyer, 1_438);
+        assert_eq!(split_buy(1_451).unwrap().tokens_to_buyer, 1_439);
+    }
+
+    #[test]
+    fn dust_buys_are_rejected_instead_of_minting_nothing() {
+        assert!(buy_amounts(MIN_BUY_AMOUNT, u128::MAX, 9).is_err());
+    }
+
+    #[test]
+    fn sell_amount_bounds() {
+        let min_sell = whole_tokens(MIN_SELL_AMOUNT, 9).unwrap();
+        let (amounts, sol_amount) = sell_amounts(min_sell, min_price(), 9).unwrap();
+        assert_eq!(amounts.sell_burn, 100_000);
+        assert_eq!(amounts.tokens_to_convert, min_sell - 100_000);
+        assert_eq!(sol_amount, 99);
+
+        // Selling the whole u64 range at the highest price no longer overflows
+        // the intermediate, only the u64 result
+        assert!(sell_amounts(u64::MAX, lamports(TOKEN_PRECISION), 9).is_ok());
+        assert!(sell_amounts(u64::MAX, u128::MAX, 9).is_err());
+        assert!(sell_amounts(min_sell, 0, 9).is_err());
+    }
+
+    #[test]
+    fn sell_rounds_against_the_seller() {
+        let (amounts, sol_amount) = sell_amounts(whole_tokens(MIN_SELL_AMOUNT, 9).unwrap() + 1, min_price(), 9).unwrap();
+        assert_eq!(amounts.sell_burn, 100_001);
+        assert_eq!(sol_amount, 99);
+    }
+
+    #[test]
+    fn early_sell_tax_decays_to_zero_over_the_period() {
+        assert_eq!(early_sell_tax_bps(0), EARLY_SELL_TAX_BPS);
+        assert_eq!(early_sell_tax_bps(-5), EARLY_SELL_TAX_BPS);
+        assert_eq!(early_sell_tax_bps(EARLY_SELL_TAX_PERIOD / 2), EARLY_SELL_TAX_BPS / 2);
+        assert_eq!(early_sell_tax_bps(EARLY_SELL_TAX_PERIOD - 1), 1);
+        assert_eq!(early_sell_tax_bps(EARLY_SELL_TAX_PERIOD), 0);
+        assert_eq!(early_sell_tax_bps(i64::MAX), 0);
+
+        let mut last = EARLY_SELL_TAX_BPS;
+        for held_for in (0..EARLY_SELL_TAX_PERIOD).step_by(600) {
+            let bps = early_sell_tax_bps(held_for);
+            assert!(bps <= last);
+            last = bps;
+        }
+    }
+
+    #[test]
+    fn wallets_that_never_bought_pay_the_full_early_sell_tax() {
+        // Tokens transferred in from a wallet that just bought
+        assert_eq!(early_sell_tax_bps(held_for(0, 1_700_000_000)), EARLY_SELL_TAX_BPS);
+        assert_eq!(early_sell_tax_bps(held_for(1_700_000_000 - EARLY_SELL_TAX_PERIOD, 1_700_000_000)), 0);
+    }
+
+    #[test]
+    fn early_sell_tax_is_taken_from_the_converted_tokens() {
+        let token_amount = whole_tokens(1_000, 9).unwrap();
+        let flipped = split_sell(token_amount, EARLY_SELL_TAX_BPS).unwrap();
+        let held = split_sell(token_amount, 0).unwrap();
+        assert_eq!(flipped.sell_burn, held.sell_burn);
+        assert_eq!(held.early_sell_tax, 0);
+        assert_eq!(flipped.early_sell_tax, fee(token_amount, EARLY_SELL_TAX_BPS, Rounding::Up).unwrap());
+        assert_eq!(flipped.sell_burn + flipped.early_sell_tax + flipped.tokens_to_convert, token_amount);
+    }
+
+    #[test]
+    fn hold_start_is_weighted_by_tokens() {
+        // First buy starts the clock
+        assert_eq!(blended_hold_start(0, 0, 100, 1_000).unwrap(), 1_000);
+        // Doubling the balance halfway through moves the start halfway
+        assert_eq!(blended_hold_start(100, 1_000, 100, 2_000).unwrap(), 1_500);
+        // Dust top-ups barely move a large, old balance
+        assert_eq!(blended_hold_start(1_000_000, 1_000, 1, 2_000).unwrap(), 1_001);
+        assert_eq!(blended_hold_start(0, 0, 0, 1_000).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn balances_without_a_recorded_buy_count_as_just_acquired() {
+        // Tokens transferred into a wallet that never bought don't date from 1970
+        assert_eq!(blended_hold_start(1_000_000, 0, 1, 2_000).unwrap(), 2_000);
+        assert_eq!(held_for(blended_hold_start(1_000_000, 0, 1, 2_000).unwrap(), 2_000), 0);
+    }
+
+    #[test]
+    fn tokens_beyond_the_recorded_balance_count_as_just_acquired() {
+        // Everything accounted for keeps its age
+        assert_eq!(effective_hold_start(100, 100, 1_000, 2_000).unwrap(), 1_000);
+        assert_eq!(effective_hold_start(50, 100, 1_000, 2_000).unwrap(), 1_000);
+        // An aged wallet receiving as much again by transfer ages only half of it
+        assert_eq!(effective_hold_start(200, 100, 1_000, 2_000).unwrap(), 1_500);
+        // A wallet that never bought
+        assert_eq!(effective_hold_start(100, 0, 0, 2_000).unwrap(), 2_000);
+        assert_eq!(effective_hold_start(0, 0, 0, 2_000).unwrap(), 2_000);
+    }
+
+    #[test]
+    fn fractional_prices_change_the_fill() {
+        let whole = buy_amounts(LAMPORTS_PER_SOL, lamports(10_000), 9).unwrap();
+        let fractional = buy_amounts(LAMPORTS_PER_SOL, lamports(10_000) + PRICE_SCALE / 2, 9).unwrap();
+        assert!(fractional.tokens_before_fees < whole.tokens_before_fees);
+    }
+
+    #[test]
+    fn thresholds_scale_with_mint_decimals() {
+        assert_eq!(whole_tokens(MIN_SELL_AMOUNT, 9).unwrap(), 1_000_000_000);
+        assert_eq!(whole_tokens(MIN_SELL_AMOUNT, 6).unwrap(), 1_000_000);
+        assert_eq!(whole_tokens(MIN_AIRDROP_ELIGIBLE, 9).unwrap(), 10_000_000_000_000);
+        assert_eq!(whole_tokens(MIN_AIRDROP_ELIGIBLE, 6).unwrap(), 10_000_000_000);
+        assert_eq!(whole_tokens(MIN_SELL_AMOUNT, 0).unwrap(), 1);
+        assert!(whole_tokens(MIN_AIRDROP_ELIGIBLE, MAX_TOKEN_DECIMALS).is_err());
+        assert!(token_unit(MAX_TOKEN_DECIMALS + 1).is_err());
+    }
+
+    #[test]
+    fn six_and_nine_decimal_mints_get_the_same_whole_tokens() {
+        let price = lamports(10_000);
+        let nine = buy_amounts(LAMPORTS_PER_SOL, price, 9).unwrap();
+        let six = buy_amounts(LAMPORTS_PER_SOL, price, 6).unwrap();
+        assert_eq!(nine.tokens_before_fees, 100_000 * 1_000_000_000);
+        assert_eq!(six.tokens_before_fees, 100_000 * 1_000_000);
+        assert_eq!(nine.tokens_to_buyer / 1_000, six.tokens_to_buyer);
+
+        let (_, nine) = sell_amounts(whole_tokens(100_000, 9).unwrap(), price, 9).unwrap();
+        let (_, six) = sell_amounts(whole_tokens(100_000, 6).unwrap(), price, 6).unwrap();
+        assert_eq!(nine, six);
+        assert_eq!(nine, 999_900_000);
+    }
+
+    #[test]
+    fn six_decimal_mints_keep_small_sells_exact() {
+        let (amounts, sol_amount) = sell_amounts(whole_tokens(MIN_SELL_AMOUNT, 6).unwrap(), min_price(), 6).unwrap();
+        assert_eq!(amounts.sell_burn, 100);
+        assert_eq!(amounts.tokens_to_convert, 999_900);
+        assert_eq!(sol_amount, 99);
+    }
+
+    #[test]
+    fn reserve_share_is_pro_rata_and_rounds_down() {
+        assert_eq!(reserve_share(100, 1, 3).unwrap(), 33);
+        assert_eq!(reserve_share(100, 3, 3).unwrap(), 100);
+        assert_eq!(reserve_share(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+        assert!(reserve_share(100, 1, 0).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+//! Price observations for time-weighted average prices.
+//!
+//! The ring keeps a running sum of price * seconds. Every trade adds the time
+//! the previous price was in force, so the difference between two points of
+//! the sum divided by the time between them is the average price over that
+//! span, and moving it needs a price held over time rather than for a single
+//! transaction.
+//!
+//! Each slot covers one `OBSERVATION_SPACING` bucket: trades within a bucket
+//! overwrite its slot, so a busy market can't push history out of the ring
+//! faster than one slot per bucket. The sum is exact at every kept
+//! observation, but the prices in force between two of them are lost, so
+//! points in between are interpolated to bucket resolution.
+
+use anchor_lang::prelude::*;
+
+use crate::math::{self, Rounding};
+use crate::ErrorCode;
+
+pub const OBSERVATION_SLOTS: usize = 144;
+/// 144 five-minute buckets keep at least 11 hours of history
+pub const OBSERVATION_SPACING: i64 = 5 * 60;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct Observation {
+    pub timestamp: i64,
+    pub cumulative_price: u128,    // Sum of price * seconds since the ring was created
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub struct ObservationRing {
+    pub observations: [Observation; OBSERVATION_SLOTS],
+    pub latest: u16,               // Index of the newest observation
+    pub len: u16,                  // Slots written so far, up to OBSERVATION_SLOTS
+    pub last_price: u128,          // Price in force since the newest observation
+}
+
+impl ObservationRing {
+    pub fn new(price: u128, now: i64) -> Self {
+        let mut observations = [Observation::default(); OBSERVATION_SLOTS];
+        observations[0].timestamp = now;
+        Self { observations, latest: 0, len: 1, last_price: price }
+    }
+
+    fn newest(&self) -> Observation {
+        self.observations[self.latest as usize]
+    }
+
+    /// The `age`-th newest observation, 0 being the newest
+    fn nth_newest(&self, age: usize) -> Observation {
+        let index = (self.latest as usize + OBSERVATION_SLOTS - age) % OBSERVATION_SLOTS;
+        self.observations[index]
+    }
+
+    /// Record that the price changed to `price` at `now`
+    pub fn record(&mut self, price: u128, now: i64) -> Result<()> {
+        let newest = self.newest();
+        let now = now.max(newest.timestamp);
+        let observation = Observation { timestamp: now, cumulative_price: self.cumulative_at(now)? };
+
+        if now / OBSERVATION_SPACING != newest.timestamp / OBSERVATION_SPACING {
+            self.latest = ((self.latest as usize + 1) % OBSERVATION_SLOTS) as u16;
+            self.len = (self.len + 1).min(OBSERVATION_SLOTS as u16);
+        }
+        self.observations[self.latest as usize] = observation;
+        self.last_price = price;
+        Ok(())
+    }
+
+    /// Cumulative price at `timestamp`: exact at and after the newest
+    /// observation, interpolated between older ones. Fails if `timestamp` is
+    /// older than the oldest observation kept.
+    pub fn cumulative_at(&self, timestamp: i64) -> Result<u128> {
+        let newest = self.newest();
+        if timestamp >= newest.timestamp {
+            let elapsed = (timestamp - newest.timestamp) as u128;
+            let accrued = self.last_price.checked_mul(elapsed).ok_or(ErrorCode::ArithmeticOverflow)?;
+            return Ok(newest.cumulative_price.checked_add(accrued).ok_or(ErrorCode::ArithmeticOverflow)?);
+        }
+
+        let mut after = newest;
+        for age in 1..self.len as usize {
+            let before = self.nth_newest(age);
+            if before.timestamp <= timestamp {
+                // Trades between the two observations may have overwritten
+                // each other, so spread the sum evenly over the span. This is
+                // exact only if the price held; otherwise it's off by at most
+                // the span times the spread of prices within it.
+                let span = (after.timestamp - before.timestamp) as u128;
+                let delta = after.cumulative_price - before.cumulative_price;
+                let part = math::mul_div_u128(delta, (timestamp - before.timestamp) as u128, span, Rounding::Down)?;
+                return Ok(before.cumulative_price + part);
+            }
+            after = before;
+        }
+        err!(ErrorCode::InvalidTwapWindow)
+    }
+
+    /// Time-weighted average price over the `window` seconds up to `now`
+    pub fn twap(&self, window: i64, now: i64) -> Result<u128> {
+        require!(window > 0, ErrorCode::InvalidTwapWindow);
+        let start = now.checked_sub(window).ok_or(ErrorCode::InvalidTwapWindow)?;
+        let delta = self.cumulative_at(now)? - self.cumulative_at(start)?;
+        Ok(delta / window as u128)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const START: i64 = 1_700_000_100;
+
+    #[test]
+    fn constant_price_averages_to_itself() {
+        let ring = ObservationRing::new(1_000, START);
+        assert_eq!(ring.twap(60, START + 3_600).unwrap(), 1_000);
+    }
+
+    #[test]
+    fn averages_weight_each_price_by_how_long_it_held() {
+        let mut ring = ObservationRing::new(1_000, START);
+        ring.record(4_000, START + 3 * OBSERVATION_SPACING).unwrap();
+        // 1,000 for three buckets then 4,000 for one
+        assert_eq!(ring.twap(4 * OBSERVATION_SPACING, START + 4 * OBSERVATION_SPACING).unwrap(), 1_750);
+        // Windows starting between observations are interpolated
+        assert_eq!(ring.twap(2 * OBSERVATION_SPACING, START + 4 * OBSERVATION_SPACING).unwrap(), 2_500);
+    }
+
+    #[test]
+    fn a_one_block_spike_barely_moves_the_average() {
+        let mut ring = ObservationRing::new(1_000, START);
+        let spike_at = START + 10 * OBSERVATION_SPACING;
+        ring.record(1_000_000, spike_at).unwrap();
+        ring.record(1_000, spike_at + 1).unwrap();
+        let twap = ring.twap(10 * OBSERVATION_SPACING, spike_at + OBSERVATION_SPACING).unwrap();
+        assert!(twap < 1_400, "twap {}", twap);
+    }
+
+    #[test]
+    fn trades_in_one_bucket_share_a_slot() {
+        let mut ring = ObservationRing::new(1_000, START);
+        for second in 1..OBSERVATION_SPACING / 2 {
+            ring.record(1_000 + second as u128, START + second).unwrap();
+        }
+        assert!(ring.len <= 2);
+    }
+
+    #[test]
+    fn sums_are_exact_at_observations_and_interpolated_between_them() {
+        let mut ring = ObservationRing::new(1_000, START);
+        ring.record(5_000, START + OBSERVATION_SPACING).unwrap();
+        // Overwrites the 5,000 observation, which held for only ten seconds
+        ring.record(1_000, START + OBSERVATION_SPACING + 10).unwrap();
+
+        // A window starting at a kept observation sees every price
+        let exact = (1_000 * 300 + 5_000 * 10 + 1_000 * 290) / 600;
+        assert_eq!(ring.twap(2 * OBSERVATION_SPACING, START + 2 * OBSERVATION_SPACING).unwrap(), exact);
+
+        // One starting inside the span only gets an even share of it: the
+        // 5,000 held for all ten of the window's first seconds, but
+        // interpolation spreads it over the whole 310-second span
+        let actual = (5_000 * 10 + 1_000 * 290) / 300;
+        let twap = ring.twap(OBSERVATION_SPACING, START + 2 * OBSERVATION_SPACING).unwrap();
+        assert!(twap > 1_000 && twap < actual, "twap {}", twap);
+    }
+
+    #[test]
+    fn windows_beyond_the_history_are_rejected() {
+        let mut ring = ObservationRing::new(1_000, START);
+        assert!(ring.twap(3_600, START + 60).is_err());
+        assert!(ring.twap(0, START + 60).is_err());
+
+        // Once the ring wraps, the oldest buckets are gone
+        for bucket in 1..=2 * OBSERVATION_SLOTS as i64 {
+            ring.record(1_000, START + bucket * OBSERVATION_SPACING).unwrap();
+        }
+        let now = START + 2 * OBSERVATION_SLOTS as i64 * OBSERVATION_SPACING;
+        assert_eq!(ring.len as usize, OBSERVATION_SLOTS);
+        assert_eq!(ring.twap(11 * 60 * 60, now).unwrap(), 1_000);
+        assert!(ring.twap(24 * 60 * 60, now).is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! Reward-per-token accounting for the staking pool.
+//!
+//! Fees credited to the pool raise a global reward-per-token accumulator.
+//! Each stake remembers the accumulator value it was last settled at, so its
+//! earnings are `staked * (current - paid)` no matter how many distributions
+//! happened in between. The pool keeps one accumulator per reward currency:
+//! the stakers' share of the dev fee is paid in SOL from the vault and their
+//! share of the sell fee is minted in tokens when they claim.
+
+use anchor_lang::prelude::*;
+
+use crate::math::{self, Rounding};
+use crate::ErrorCode;
+
+/// Fixed-point scale of the reward-per-token accumulator
+pub const REWARD_SCALE: u128 = 1_000_000_000_000;
+
+/// Accumulator increase for `amount` reward tokens shared by `total_staked` tokens
+pub fn reward_per_token_increase(amount: u64, total_staked: u64) -> Result<u128> {
+    require!(total_staked > 0, ErrorCode::ArithmeticOverflow);
+    math::mul_div_u128(amount as u128, REWARD_SCALE, total_staked as u128, Rounding::Down)
+}
+
+/// Rewards earned by `staked` tokens since the accumulator was at `paid`
+pub fn earned(staked: u64, reward_per_token: u128, paid: u128) -> Result<u64> {
+    let delta = reward_per_token.checked_sub(paid).ok_or(ErrorCode::ArithmeticOverflow)?;
+    math::to_u64(math::mul_div_u128(staked as u128, delta, REWARD_SCALE, Rounding::Down)?)
+}
+
+/// One reward stream shared pro rata by everything staked
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct RewardAccumulator {
+    pub reward_per_token: u128,    // Rewards per staked token * REWARD_SCALE
+    pub queued: u64,               // Rewards credited while nothing was staked
+    pub total: u64,                // Rewards credited over the pool's lifetime
+}
+
+impl RewardAccumulator {
+    pub fn credit(&mut self, reward: u64, total_staked: u64) -> Result<()> {
+        self.total = self.total.checked_add(reward).ok_or(ErrorCode::ArithmeticOverflow)?;
+        self.queued = self.queued.checked_add(reward).ok_or(ErrorCode::ArithmeticOverflow)?;
+        self.distribute_queued(total_staked)
+    }
+
+    /// Spread queued rewards over `total_staked`, if anything is staked
+    pub fn distribute_queued(&mut self, total_staked: u64) -> Result<()> {
+        if total_staked == 0 || self.queued == 0 {
+            return Ok(());
+        }
+        let increase = reward_per_token_increase(self.queued, total_staked)?;
+        self.reward_per_token = self.reward_per_token.checked_add(increase).ok_or(ErrorCode::ArithmeticOverflow)?;
+        self.queued = 0;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rewards_are_shared_pro_rata() {
+        // 300 staked by A and 100 by B share 1,000 reward tokens
+        let rpt = reward_per_token_increase(1_000, 400).unwrap();
+        assert_eq!(earned(300, rpt, 0).unwrap(), 750);
+        assert_eq!(earned(100, rpt, 0).unwrap(), 250);
+    }
+
+    #[test]
+    fn late_stakers_only_earn_later_rewards() {
+        let first = reward_per_token_increase(1_000, 100).unwrap();
+        // B joins after the first distribution, with the accumulator at `first`
+        let second = first + reward_per_token_increase(1_000, 200).unwrap();
+        assert_eq!(earned(100, second, 0).unwrap(), 1_500);
+        assert_eq!(earned(100, second, first).unwrap(), 500);
+    }
+
+    #[test]
+    fn payouts_never_exceed_what_was_distributed() {
+        let rpt = reward_per_token_increase(10, 3).unwrap();
+        let paid: u64 = (0..3).map(|_| earned(1, rpt, 0).unwrap()).sum();
+        assert!(paid <= 10);
+        assert_eq!(paid, 9);
+    }
+
+    #[test]
+    fn rewards_credited_before_anyone_stakes_wait_for_the_first_stakers() {
+        let mut rewards = RewardAccumulator::default();
+        rewards.credit(1_000, 0).unwrap();
+        assert_eq!((rewards.reward_per_token, rewards.queued), (0, 1_000));
+
+        rewards.distribute_queued(500).unwrap();
+        assert_eq!(earned(500, rewards.reward_per_token, 0).unwrap(), 1_000);
+        assert_eq!(rewards.queued, 0);
+        assert_eq!(rewards.total, 1_000);
+    }
+
+    #[test]
+    fn accumulator_handles_the_full_supply_range() {
+        let rpt = reward_per_token_increase(u64::MAX, 1).unwrap();
+        assert_eq!(earned(1, rpt, 0).unwrap(), u64::MAX);
+        assert_eq!(reward_per_token_increase(1, u64::MAX).unwrap(), 0);
+        assert!(reward_per_token_increase(1, 0).is_err());
+        assert!(earned(1, 0, 1).is_err());
+    }
+}
@@ -0,0 +1,112 @@
+//! Token supply accounting.
+//!
+//! Buys issue tokens on the curve but only mint the buyer's share; the burn
+//! fee is never minted at all (an implicit burn). Sells burn real tokens from
+//! the seller's account (an explicit burn). Keeping the two apart lets the
+//! ledger reconcile exactly with the mint: circulating supply is whatever the
+//! mint held when the program took it over, plus everything the program
+//! minted, less everything explicitly burned.
+
+use anchor_lang::prelude::*;
+
+use crate::ErrorCode;
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, Default, PartialEq, Eq, InitSpace)]
+pub struct SupplyLedger {
+    pub initial_supply: u64,       // Mint supply when the program took it over
+    pub minted: u64,               // Everything the program minted, airdrops included
+    pub implicit_burned: u64,      // Issued by the curve but never minted
+    pub explicit_burned: u64,      // Burned from holders' token accounts
+    pub airdropped: u64,           // Minted to airdrop winners
+}
+
+/// The ledger next to the mint's own supply
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SupplyReport {
+    pub ledger: SupplyLedger,
+    pub circulating: u64,
+    pub mint_supply: u64,
+    pub reconciled: bool,          // The mint supply matches the ledger's circulating supply
+}
+
+impl SupplyLedger {
+    pub fn new(initial_supply: u64) -> Self {
+        Self { initial_supply, ..Self::default() }
+    }
+
+    pub fn record_mint(&mut self, amount: u64) -> Result<()> {
+        self.minted = self.minted.checked_add(amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_airdrop(&mut self, amount: u64) -> Result<()> {
+        self.record_mint(amount)?;
+        self.airdropped = self.airdropped.checked_add(amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_implicit_burn(&mut self, amount: u64) -> Result<()> {
+        self.implicit_burned = self.implicit_burned.checked_add(amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    pub fn record_explicit_burn(&mut self, amount: u64) -> Result<()> {
+        self.explicit_burned = self.explicit_burned.checked_add(amount).ok_or(ErrorCode::ArithmeticOverflow)?;
+        Ok(())
+    }
+
+    /// Tokens that should exist according to the ledger
+    pub fn circulating(&self) -> Result<u64> {
+        self.initial_supply
+            .checked_add(self.minted)
+            .and_then(|total| total.checked_sub(self.explicit_burned))
+            .ok_or(error!(ErrorCode::ArithmeticOverflow))
+    }
+
+    pub fn reconcile(&self, mint_supply: u64) -> Result<SupplyReport> {
+        let circulating = self.circulating()?;
+        Ok(SupplyReport {
+            ledger: *self,
+            circulating,
+            mint_supply,
+            reconciled: circulating == mint_supply,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn circulating_follows_mints_and_explicit_burns_only() {
+        let mut ledger = SupplyLedger::new(1_000);
+        ledger.record_mint(500).unwrap();
+        ledger.record_airdrop(200).unwrap();
+        ledger.record_implicit_burn(50).unwrap();
+        ledger.record_explicit_burn(300).unwrap();
+
+        assert_eq!(ledger.minted, 700);
+        assert_eq!(ledger.airdropped, 200);
+        assert_eq!(ledger.circulating().unwrap(), 1_400);
+    }
+
+    #[test]
+    fn reconcile_flags_supply_the_ledger_did_not_see() {
+        let mut ledger = SupplyLedger::new(0);
+        ledger.record_mint(1_000).unwrap();
+        assert!(ledger.reconcile(1_000).unwrap().reconciled);
+
+        let report = ledger.reconcile(1_001).unwrap();
+        assert!(!report.reconciled);
+        assert_eq!(report.circulating, 1_000);
+        assert_eq!(report.mint_supply, 1_001);
+    }
+
+    #[test]
+    fn burning_more_than_exists_is_an_error() {
+        let mut ledger = SupplyLedger::new(10);
+        ledger.record_explicit_burn(11).unwrap();
+        assert!(ledger.circulating().is_err());
+    }
+}
@@ -0,0 +1,88 @@
+//! Linear vesting with a cliff.
+//!
+//! A schedule vests `total` tokens evenly over `duration` seconds from its
+//! start. Nothing can be claimed before the cliff; at the cliff everything
+//! vested so far unlocks at once.
+//!
+//! Schedules draw on a vesting allocation fixed when the pool is created, so
+//! the authority can never commit more tokens than it announced up front.
+
+use anchor_lang::prelude::*;
+
+use crate::math::{self, Rounding};
+use crate::ErrorCode;
+
+pub fn validate(total: u64, cliff: i64, duration: i64) -> Result<()> {
+    require!(total > 0 && duration > 0, ErrorCode::InvalidVesting);
+    require!(cliff >= 0 && cliff <= duration, ErrorCode::InvalidVesting);
+    Ok(())
+}
+
+/// Allocation used once a schedule of `total` is added to `allocated`,
+/// failing if that would exceed `allocation`
+pub fn allocate(allocated: u64, total: u64, allocation: u64) -> Result<u64> {
+    let allocated = allocated.checked_add(total).ok_or(ErrorCode::ArithmeticOverflow)?;
+    require!(allocated <= allocation, ErrorCode::VestingAllocationExceeded);
+    Ok(allocated)
+}
+
+/// Tokens of `total` vested at `now` for a schedule starting at `start`
+pub fn vested_amount(total: u64, start: i64, cliff: i64, duration: i64, now: i64) -> Result<u64> {
+    let elapsed = now.saturating_sub(start);
+    if elapsed < cliff {
+        return Ok(0);
+    }
+    if elapsed >= duration {
+        return Ok(total);
+    }
+    math::mul_div(total, elapsed as u64, duration as u64, Rounding::Down)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: i64 = 24 * 60 * 60;
+    const TOTAL: u64 = 1_200_000;
+
+    #[test]
+    fn nothing_vests_before_the_cliff() {
+        assert_eq!(vested_amount(TOTAL, 1_000, 90 * DAY, 360 * DAY, 1_000).unwrap(), 0);
+        assert_eq!(vested_amount(TOTAL, 1_000, 90 * DAY, 360 * DAY, 1_000 + 90 * DAY - 1).unwrap(), 0);
+        assert_eq!(vested_amount(TOTAL, 1_000, 90 * DAY, 360 * DAY, 0).unwrap(), 0);
+    }
+
+    #[test]
+    fn the_cliff_releases_everything_vested_so_far() {
+        assert_eq!(vested_amount(TOTAL, 0, 90 * DAY, 360 * DAY, 90 * DAY).unwrap(), TOTAL / 4);
+    }
+
+    #[test]
+    fn vesting_is_linear_and_completes_at_the_end() {
+        assert_eq!(vested_amount(TOTAL, 0, 0, 360 * DAY, 180 * DAY).unwrap(), TOTAL / 2);
+        assert_eq!(vested_amount(TOTAL, 0, 0, 360 * DAY, 360 * DAY).unwrap(), TOTAL);
+        assert_eq!(vested_amount(TOTAL, 0, 0, 360 * DAY, i64::MAX).unwrap(), TOTAL);
+        // Rounds down until the very end
+        assert_eq!(vested_amount(10, 0, 0, 3, 1).unwrap(), 3);
+    }
+
+    #[test]
+    fn validate_rejects_impossible_schedules() {
+        assert!(validate(TOTAL, 90 * DAY, 360 * DAY).is_ok());
+        assert!(validate(TOTAL, 0, 1).is_ok());
+        assert!(validate(TOTAL, 360 * DAY, 360 * DAY).is_ok());
+        assert!(validate(0, 90 * DAY, 360 * DAY).is_err());
+        assert!(validate(TOTAL, 0, 0).is_err());
+        assert!(validate(TOTAL, -1, 360 * DAY).is_err());
+        assert!(validate(TOTAL, 360 * DAY + 1, 360 * DAY).is_err());
+    }
+
+    #[test]
+    fn schedules_cannot_exceed_the_allocation() {
+        assert_eq!(allocate(0, TOTAL, TOTAL).unwrap(), TOTAL);
+        assert_eq!(allocate(TOTAL / 2, TOTAL / 2, TOTAL).unwrap(), TOTAL);
+        assert!(allocate(TOTAL / 2, TOTAL / 2 + 1, TOTAL).is_err());
+        assert!(allocate(TOTAL, 1, TOTAL).is_err());
+        assert!(allocate(u64::MAX, 1, u64::MAX).is_err());
+    }
+}
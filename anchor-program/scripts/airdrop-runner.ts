@@ -60,7 +60,8 @@ function parseProgramState(data: Buffer): ProgramStateData {
   let offset = 8; // Skip Anchor discriminator
   const authority = new PublicKey(data.slice(offset, offset + 32)); offset += 32;
   const tokenMint = new PublicKey(data.slice(offset, offset + 32)); offset += 32;
-  const currentPrice = readU64LE(data, offset); offset += 8;
+  // u128 fixed-point: lamports per token scaled by 1e9
+  const currentPrice = readU64LE(data, offset) | (readU64LE(data, offset + 8) << 64n); offset += 16;
   const totalBurned = readU64LE(data, offset); offset += 8;
   const totalBuys = readU64LE(data, offset); offset += 8;
   const nextAirdropTime = readI64LE(data, offset); offset += 8;
@@ -211,6 +212,10 @@ async function runAirdropCycle(
       [Buffer.from("last-airdrop")],
       program.programId
     );
+    const [statsPda] = PublicKey.findProgramAddressSync(
+      [Buffer.from("stats")],
+      program.programId
+    );
     const tx = await program.methods
       .airdrop()
       .accounts({
@@ -219,6 +224,7 @@ async function runAirdropCycle(
         authority: provider.wallet.publicKey,
         recipientTokenAccount: winner.tokenAccount,
         lastAirdrop: lastAirdropPda,
+        stats: statsPda,
         tokenProgram: TOKEN_PROGRAM_ID,
         systemProgram: SystemProgram.programId,
       })
@@ -84,23 +84,25 @@ async function main() {
   const tokenMint = new PublicKey(data.slice(offset, offset + 32)); offset += 32;
 
   const view = new DataView(data.buffer, data.byteOffset, data.byteLength);
-  const currentPrice = view.getBigUint64(offset, true); offset += 8;
+  const currentPrice = view.getBigUint64(offset, true) | (view.getBigUint64(offset + 8, true) << 64n); offset += 16;
   const totalBurned = view.getBigUint64(offset, true); offset += 8;
   const totalBuys = view.getBigUint64(offset, true); offset += 8;
   const nextAirdropTime = view.getBigInt64(offset, true); offset += 8;
   const airdropAmount = view.getBigUint64(offset, true); offset += 8;
   const lastAirdropCycle = view.getBigInt64(offset, true); offset += 8;
   const airdropExecuted = data[offset] !== 0; offset += 1;
-  const bump = data[offset];
+  const bump = data[offset]; offset += 1;
+  const solReserve = view.getBigUint64(offset, true); offset += 8;
 
   console.log(`   Authority: ${authority.toString()}`);
   console.log(`   Token Mint: ${tokenMint.toString()}`);
-  console.log(`   Current Price: ${currentPrice.toString()} lamports`);
+  console.log(`   Current Price: ${Number(currentPrice) / 1e9} lamports (scaled ${currentPrice.toString()})`);
   console.log(`   Total Burned: ${Number(totalBurned) / 1e9} tokens`);
   console.log(`   Total Buys: ${totalBuys.toString()}`);
   console.log(`   Next Airdrop: ${new Date(Number(nextAirdropTime) * 1000).toISOString()}`);
   console.log(`   Airdrop Pot: ${Number(airdropAmount) / 1e9} tokens`);
   console.log(`   Airdrop Executed: ${airdropExecuted}`);
+  console.log(`   SOL Reserve: ${Number(solReserve) / 1e9} SOL`);
 
   // Check for any PurchaseOrder accounts
   console.log("\n📦 Purchase Orders:");
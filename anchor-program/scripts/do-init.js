@@ -44,13 +44,17 @@ async function main() {
   // Discriminator for "initialize" = first 8 bytes of sha256("global:initialize")
   const discriminator = Buffer.from([175, 175, 109, 31, 13, 152, 155, 237]);
   
-  // Args: initial_price: u64, airdrop_amount: u64
+  // Args: initial_price: u64, airdrop_amount: u64, curve: CurveKind
   const initialPrice = new BN(10000); // 0.00001 SOL per token
   const airdropAmount = new BN(1000000000000); // 1000 tokens per airdrop
   const priceBuffer = initialPrice.toArrayLike(Buffer, "le", 8);
   const airdropBuffer = airdropAmount.toArrayLike(Buffer, "le", 8);
+
+  // CurveKind::Linear (variant 0) { increment: u128, decrement: u128 }, 1 lamport per SOL traded
+  const priceStep = new BN(1000000000).toArrayLike(Buffer, "le", 16);
+  const curveBuffer = Buffer.concat([Buffer.from([0]), priceStep, priceStep]);
   
-  const data = Buffer.concat([discriminator, priceBuffer, airdropBuffer]);
+  const data = Buffer.concat([discriminator, priceBuffer, airdropBuffer, curveBuffer]);
   
   // Accounts for simplified Initialize (must match order in lib.rs)
   const keys = [
@@ -90,8 +90,12 @@ if (idl.address) {
   try {
     // airdrop_amount is stored in base units (9 decimals)
     const airdropAmount = new anchor.BN(100_000_000_000); // 100 tokens per airdrop winner
+    // Linear curve: +/- 1 lamport per token for every SOL traded (fixed-point, 1e9 scale)
+    const curve = {
+      linear: { increment: new anchor.BN(1_000_000_000), decrement: new anchor.BN(1_000_000_000) },
+    };
     const tx = await program.methods
-      .initialize(bondingCurvePrice, airdropAmount)
+      .initialize(bondingCurvePrice, airdropAmount, curve)
       .accounts({
         programState: programState,
         tokenMint: tokenMint,
@@ -108,13 +112,45 @@ if (idl.address) {
     throw err;
   }
 
-  // 4. Transfer mint authority to program
+  // 4. Create the staking pool (trades credit it, so it must exist before the first buy)
+  console.log("\n🥩 Initializing staking pool...");
+  // Stakers get 50% of the dev fee and the sell fee; unstaking waits a day after the last stake
+  const stakingTx = await program.methods
+    .initializeStaking(50_000, new anchor.BN(24 * 60 * 60))
+    .accounts({
+      tokenMint: tokenMint,
+      authority: wallet.publicKey,
+    })
+    .rpc();
+  console.log("✅ Staking initialized:", stakingTx);
+
+  // 5. Create the stats account (trades and airdrops update it)
+  console.log("\n📊 Initializing stats...");
+  const statsTx = await program.methods
+    .initializeStats()
+    .accounts({
+      authority: wallet.publicKey,
+    })
+    .rpc();
+  console.log("✅ Stats initialized:", statsTx);
+
+  // 6. Create the price oracle (trades record observations in it)
+  console.log("\n📈 Initializing price oracle...");
+  const oracleTx = await program.methods
+    .initializeOracle()
+    .accounts({
+      authority: wallet.publicKey,
+    })
+    .rpc();
+  console.log("✅ Price oracle initialized:", oracleTx);
+
+  // 7. Transfer mint authority to program
   console.log("\n🔐 Transferring mint authority to program PDA...");
   // This would require calling setAuthority from SPL token
   console.log("⚠️  Manual step: Run this command:");
   console.log(`spl-token authorize ${tokenMint.toString()} mint ${programState.toString()}`);
 
-  // 5. Display summary
+  // 8. Display summary
   console.log("\n" + "=".repeat(60));
   console.log("✅ FOSSR INITIALIZATION COMPLETE!");
   console.log("=".repeat(60));
@@ -1,6 +1,7 @@
 import * as anchor from "@coral-xyz/anchor";
 import { Program } from "@coral-xyz/anchor";
 import { Fossr } from "../target/types/fossr";
+import { Token, TOKEN_PROGRAM_ID } from "@solana/spl-token";
 import { assert } from "chai";
 
 describe("fossr", () => {
@@ -26,6 +27,36 @@ describe("fossr", () => {
     );
   });
 
+  // Purchase order a buy for `owner` at `timestamp` creates
+  const purchaseOrderFor = (owner: anchor.web3.PublicKey, timestamp: number) =>
+    anchor.web3.PublicKey.findProgramAddressSync(
+      [Buffer.from("order"), owner.toBuffer(), new anchor.BN(timestamp).toArrayLike(Buffer, "le", 8)],
+      program.programId
+    )[0];
+
+  // Buy `lamports` worth of tokens signed by `buyer`. `extra` overrides or adds
+  // accounts, such as a different beneficiary or a referrer.
+  const buyTokens = (
+    buyer: anchor.web3.Keypair,
+    lamports: number,
+    timestamp: number,
+    extra: Record<string, anchor.web3.PublicKey | null | undefined> = {}
+  ) => {
+    const beneficiary = extra.beneficiary ?? buyer.publicKey;
+    return program.methods
+      .buyTokens(new anchor.BN(lamports), new anchor.BN(timestamp))
+      .accountsPartial({
+        programState,
+        tokenMint,
+        buyer: buyer.publicKey,
+        beneficiary,
+        purchaseOrder: purchaseOrderFor(beneficiary, timestamp),
+        programVault,
+        ...extra,
+      })
+      .signers([buyer]);
+  };
+
   it("Initializes the program", async () => {
     // Create token mint
     tokenMint = await createMint(
@@ -37,48 +68,57 @@ describe("fossr", () => {
     );
 
     const bondingCurvePrice = new anchor.BN(10000); // 0.00001 SOL per token
+    const airdropAmount = new anchor.BN(100_000_000_000);
+    const curve = {
+      linear: { increment: new anchor.BN(1_000_000_000), decrement: new anchor.BN(1_000_000_000) },
+    };
+
+    const initialize = (mint: anchor.web3.PublicKey) =>
+      program.methods
+        .initialize(bondingCurvePrice, airdropAmount, curve)
+        .accounts({
+          programState,
+          tokenMint: mint,
+          authority: provider.wallet.publicKey,
+        })
+        .rpc();
 
+    // Mints the program can't mint from, or with more decimals than the math supports, are rejected
+    const foreignMint = await createMint(provider.connection, provider.wallet.payer, provider.wallet.publicKey, null, 9);
+    await expectError(initialize(foreignMint), "InvalidMint");
+    const preciseMint = await createMint(provider.connection, provider.wallet.payer, programState, null, 19);
+    await expectError(initialize(preciseMint), "InvalidMintDecimals");
+
+    await initialize(tokenMint);
+
+    // Trades credit the staking pool and update the stats and price oracle, so all have to exist before the first buy
     await program.methods
-      .initialize(bondingCurvePrice)
-      .accounts({
-        programState,
-        tokenMint,
-        authority: provider.wallet.publicKey,
-      })
+      .initializeStaking(50_000, new anchor.BN(0))
+      .accountsPartial({ programState, tokenMint, authority: provider.wallet.publicKey })
+      .rpc();
+    await program.methods
+      .initializeStats()
+      .accountsPartial({ programState, authority: provider.wallet.publicKey })
+      .rpc();
+    await program.methods
+      .initializeOracle()
+      .accountsPartial({ programState, authority: provider.wallet.publicKey })
       .rpc();
 
     const state = await program.account.programState.fetch(programState);
-    assert.equal(state.currentPrice.toNumber(), 10000);
+    // Stored as lamports per token scaled by PRICE_SCALE (1e9)
+    assert.equal(state.currentPrice.toString(), "10000000000000");
     console.log("✅ Program initialized");
   });
 
   it("Buys tokens with protective lock", async () => {
-    const buyer = provider.wallet.publicKey;
-    const solAmount = new anchor.BN(anchor.web3.LAMPORTS_PER_SOL * 0.5); // 0.5 SOL
-
-    const [purchaseOrder] = await anchor.web3.PublicKey.findProgramAddress(
-      [
-        Buffer.from("purchase-order"),
-        buyer.toBuffer(),
-        Buffer.from(new Date().getTime().toString()),
-      ],
-      program.programId
-    );
+    const buyer = (provider.wallet as anchor.Wallet).payer;
+    const timestamp = Math.floor(Date.now() / 1000);
+    await buyTokens(buyer, anchor.web3.LAMPORTS_PER_SOL * 0.5, timestamp).rpc(); // 0.5 SOL
 
-    await program.methods
-      .buyTokens(solAmount)
-      .accounts({
-        programState,
-        tokenMint,
-        buyer,
-        purchaseOrder,
-        programVault,
-      })
-      .rpc();
-
-    const order = await program.account.purchaseOrder.fetch(purchaseOrder);
+    const order = await program.account.purchaseOrder.fetch(purchaseOrderFor(buyer.publicKey, timestamp));
     assert.ok(order.tokenAmount.toNumber() > 0);
-    assert.ok(order.unlockTime > Date.now() / 1000);
+    assert.ok(order.unlockTime.toNumber() > Date.now() / 1000);
     console.log("✅ Tokens purchased with lock");
   });
 
@@ -91,8 +131,1323 @@ describe("fossr", () => {
     // This test would need a full raffle period
     console.log("⏭️  Skip raffle test (requires full period)");
   });
+
+  describe("trade guards", () => {
+    const trader = anchor.web3.Keypair.generate();
+    let traderTokenAccount: anchor.web3.PublicKey;
+
+    before(async () => {
+      await fund(provider.connection, trader.publicKey);
+      traderTokenAccount = await anchor.utils.token.associatedAddress({
+        mint: tokenMint,
+        owner: trader.publicKey,
+      });
+    });
+
+    const buyIx = (timestamp: number) => buyTokens(trader, anchor.web3.LAMPORTS_PER_SOL / 10, timestamp).instruction();
+
+    const sellIx = (amount: anchor.BN) =>
+      program.methods
+        .sellTokens(amount)
+        .accountsPartial({
+          programState,
+          tokenMint,
+          seller: trader.publicKey,
+          sellerTokenAccount: traderTokenAccount,
+          programVault,
+        })
+        .instruction();
+
+    it("rejects a buy and a sell in the same transaction", async () => {
+      // Give the trader tokens to sell from an earlier, separate buy
+      await provider.sendAndConfirm(new anchor.web3.Transaction().add(await buyIx(1)), [trader]);
+      await sleep(2_000);
+
+      const bundle = new anchor.web3.Transaction()
+        .add(await buyIx(2))
+        .add(await sellIx(new anchor.BN(1_000_000_000)));
+      await expectError(provider.sendAndConfirm(bundle, [trader]), "BuyAndSellInSameTransaction");
+    });
+
+    it("rejects a sell straight after the wallet's last buy", async () => {
+      await provider.sendAndConfirm(new anchor.web3.Transaction().add(await buyIx(3)), [trader]);
+      const sell = new anchor.web3.Transaction().add(await sellIx(new anchor.BN(1_000_000_000)));
+      await expectError(provider.sendAndConfirm(sell, [trader]), "SellTooSoonAfterBuy");
+    });
+
+    it("allows the sell once the slot gap has passed", async () => {
+      await sleep(2_000);
+      const potBefore = (await program.account.programState.fetch(programState)).airdropAmount;
+      const sell = new anchor.web3.Transaction().add(await sellIx(new anchor.BN(1_000_000_000)));
+      await provider.sendAndConfirm(sell, [trader]);
+
+      // Selling seconds after buying pays close to the full early-sell tax into the airdrop pot
+      const potAfter = (await program.account.programState.fetch(programState)).airdropAmount;
+      assert.ok(potAfter.sub(potBefore).gt(new anchor.BN(49_000_000)));
+
+      const [profile] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("buyer-profile"), trader.publicKey.toBuffer()],
+        program.programId
+      );
+      const state = await program.account.buyerProfile.fetch(profile);
+      assert.ok(state.wallet.equals(trader.publicKey));
+      assert.ok(state.lastBuySlot.toNumber() > 0);
+    });
+
+    it("taxes tokens moved to a fresh wallet as if just bought", async () => {
+      const recipient = anchor.web3.Keypair.generate();
+      await fund(provider.connection, recipient.publicKey);
+      const mint = new Token(provider.connection, tokenMint, TOKEN_PROGRAM_ID, trader);
+      const recipientTokenAccount = (await mint.getOrCreateAssociatedAccountInfo(recipient.publicKey)).address;
+      await mint.transfer(traderTokenAccount, recipientTokenAccount, trader, [], 1_000_000_000);
+
+      const sell = () =>
+        program.methods
+          .sellTokens(new anchor.BN(1_000_000_000))
+          .accountsPartial({ programState, tokenMint, seller: recipient.publicKey, sellerTokenAccount: recipientTokenAccount, programVault })
+          .signers([recipient])
+          .rpc();
+
+      let potBefore = (await program.account.programState.fetch(programState)).airdropAmount;
+      await sell();
+
+      // The full 5% early-sell tax
+      let potAfter = (await program.account.programState.fetch(programState)).airdropAmount;
+      assert.equal(potAfter.sub(potBefore).toString(), "50000000");
+
+      // A small buy doesn't backdate the transferred tokens either
+      await mint.transfer(traderTokenAccount, recipientTokenAccount, trader, [], 1_000_000_000);
+      await buyTokens(recipient, 10_000_000, 1).rpc();
+      await sleep(2_000);
+
+      potBefore = (await program.account.programState.fetch(programState)).airdropAmount;
+      await sell();
+      potAfter = (await program.account.programState.fetch(programState)).airdropAmount;
+      assert.ok(potAfter.sub(potBefore).gt(new anchor.BN(49_000_000)));
+    });
+  });
+
+  describe("circuit breaker", () => {
+    const seller = anchor.web3.Keypair.generate();
+    let sellerTokenAccount: anchor.web3.PublicKey;
+
+    const sell = () =>
+      program.methods
+        .sellTokens(new anchor.BN(1_000_000_000))
+        .accountsPartial({ programState, tokenMint, seller: seller.publicKey, sellerTokenAccount, programVault })
+        .signers([seller])
+        .rpc();
+
+    before(async () => {
+      await fund(provider.connection, seller.publicKey);
+      sellerTokenAccount = await anchor.utils.token.associatedAddress({ mint: tokenMint, owner: seller.publicKey });
+
+      await buyTokens(seller, anchor.web3.LAMPORTS_PER_SOL, 1).rpc();
+      await sleep(2_000);
+    });
+
+    after(async () => {
+      await program.methods
+        .configureCircuitBreaker(20_000, new anchor.BN(3_600), new anchor.BN(1_800))
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+    });
+
+    it("rejects settings that could never be met", async () => {
+      await expectError(
+        program.methods
+          .configureCircuitBreaker(100_000, new anchor.BN(3_600), new anchor.BN(1_800))
+          .accountsPartial({ programState, authority: provider.wallet.publicKey })
+          .rpc(),
+        "InvalidBreakerConfig"
+      );
+    });
+
+    it("halts sells after a drop and lets the guardian resume them", async () => {
+      // Any drop at all trips a 0.001% breaker
+      await program.methods
+        .configureCircuitBreaker(1, new anchor.BN(3_600), new anchor.BN(3_600))
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+
+      await sell();
+      const state = await program.account.programState.fetch(programState);
+      assert.ok(state.circuitBreaker.haltedUntil.toNumber() > Date.now() / 1000);
+      await expectError(sell(), "TradingHalted");
+
+      await expectError(
+        program.methods
+          .resumeTrading()
+          .accountsPartial({ programState, guardian: seller.publicKey })
+          .signers([seller])
+          .rpc(),
+        "Unauthorized"
+      );
+
+      await program.methods
+        .resumeTrading()
+        .accountsPartial({ programState, guardian: provider.wallet.publicKey })
+        .rpc();
+      await sell();
+    });
+
+    it("only lets the authority change the guardian", async () => {
+      await expectError(
+        program.methods
+          .setGuardian(seller.publicKey)
+          .accountsPartial({ programState, authority: seller.publicKey })
+          .signers([seller])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("sell limits", () => {
+    const seller = anchor.web3.Keypair.generate();
+    let sellerTokenAccount: anchor.web3.PublicKey;
+
+    const sell = (amount: number) =>
+      program.methods
+        .sellTokens(new anchor.BN(amount))
+        .accountsPartial({ programState, tokenMint, seller: seller.publicKey, sellerTokenAccount, programVault })
+        .signers([seller])
+        .rpc();
+
+    const setSellLimits = (maxTokens: number) =>
+      program.methods
+        .configureSellLimits(new anchor.BN(maxTokens), 0, new anchor.BN(24 * 60 * 60))
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+
+    before(async () => {
+      await fund(provider.connection, seller.publicKey);
+      sellerTokenAccount = await anchor.utils.token.associatedAddress({ mint: tokenMint, owner: seller.publicKey });
+
+      await buyTokens(seller, anchor.web3.LAMPORTS_PER_SOL, 1).rpc();
+      await sleep(2_000);
+
+      // 3 whole tokens per day
+      await setSellLimits(3_000_000_000);
+    });
+
+    after(async () => {
+      await setSellLimits(0);
+    });
+
+    it("caps the tokens a wallet can sell per window", async () => {
+      await sell(2_000_000_000);
+      await expectError(sell(2_000_000_000), "SellLimitExceeded");
+      await sell(1_000_000_000);
+    });
+
+    it("exempts whitelisted market makers", async () => {
+      await expectError(
+        program.methods
+          .setMarketMaker(seller.publicKey, true)
+          .accountsPartial({ programState, authority: seller.publicKey })
+          .signers([seller])
+          .rpc(),
+        "Unauthorized"
+      );
+
+      await program.methods
+        .setMarketMaker(seller.publicKey, true)
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+      await sell(2_000_000_000);
+
+      const [profile] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("buyer-profile"), seller.publicKey.toBuffer()],
+        program.programId
+      );
+      const state = await program.account.buyerProfile.fetch(profile);
+      assert.ok(state.marketMaker);
+      // Usage decays between the sells, so a little less than the 5 tokens sold
+      assert.isAbove(state.sellWindow.used.toNumber(), 4_990_000_000);
+      assert.isAtMost(state.sellWindow.used.toNumber(), 5_000_000_000);
+    });
+  });
+
+  describe("buy limits", () => {
+    const whale = anchor.web3.Keypair.generate();
+    const day = new anchor.BN(24 * 60 * 60);
+    const noLimits = {
+      maxSol: new anchor.BN(0),
+      maxHolding: new anchor.BN(0),
+      window: day,
+      launchDuration: new anchor.BN(0),
+      launchMaxSol: new anchor.BN(0),
+      launchMaxHolding: new anchor.BN(0),
+    };
+
+    const setBuyLimits = (limits: typeof noLimits) =>
+      program.methods
+        .configureBuyLimits(limits)
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+
+    const buy = (lamports: number, timestamp: number) => buyTokens(whale, lamports, timestamp).rpc();
+
+    before(async () => {
+      await fund(provider.connection, whale.publicKey);
+    });
+
+    afterEach(async () => {
+      await setBuyLimits(noLimits);
+    });
+
+    it("caps the SOL a wallet can spend per window", async () => {
+      await setBuyLimits({ ...noLimits, maxSol: new anchor.BN(anchor.web3.LAMPORTS_PER_SOL / 2) });
+      await buy(anchor.web3.LAMPORTS_PER_SOL / 4, 1);
+      await buy(anchor.web3.LAMPORTS_PER_SOL / 4, 2);
+      await expectError(buy(anchor.web3.LAMPORTS_PER_SOL / 10, 3), "BuyLimitExceeded");
+    });
+
+    it("caps the tokens a wallet can hold", async () => {
+      // The whale already holds tokens from the previous test
+      await setBuyLimits({ ...noLimits, maxHolding: new anchor.BN(1) });
+      await expectError(buy(anchor.web3.LAMPORTS_PER_SOL / 10, 4), "MaxHoldingExceeded");
+    });
+
+    it("applies the launch caps only during the launch phase", async () => {
+      // A one-second launch phase ended long before this test runs
+      await setBuyLimits({
+        ...noLimits,
+        launchDuration: new anchor.BN(1),
+        launchMaxSol: new anchor.BN(1),
+      });
+      await buy(anchor.web3.LAMPORTS_PER_SOL / 10, 5);
+    });
+
+    it("rejects a zero-length window", async () => {
+      await expectError(setBuyLimits({ ...noLimits, window: new anchor.BN(0) }), "InvalidLimitConfig");
+    });
+  });
+
+  describe("referrals", () => {
+    const referrer = anchor.web3.Keypair.generate();
+    const referee = anchor.web3.Keypair.generate();
+    const stranger = anchor.web3.Keypair.generate();
+
+    const profileOf = (wallet: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("buyer-profile"), wallet.toBuffer()], program.programId)[0];
+    const rewardsOf = (wallet: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("referrer-rewards"), wallet.toBuffer()], program.programId)[0];
+
+    const buy = (buyer: anchor.web3.Keypair, timestamp: number, referredBy: anchor.web3.PublicKey | null) =>
+      buyTokens(buyer, anchor.web3.LAMPORTS_PER_SOL / 10, timestamp, {
+        referrerProfile: referredBy && profileOf(referredBy),
+        referrerRewards: referredBy && rewardsOf(referredBy),
+      }).rpc();
+
+    before(async () => {
+      for (const wallet of [referrer, referee, stranger]) {
+        await fund(provider.connection, wallet.publicKey);
+      }
+      await buy(referrer, 1, null);
+    });
+
+    it("rejects a referrer that has never bought", async () => {
+      // The stranger has no profile yet
+      await expectError(buy(referee, 1, stranger.publicKey), "AccountNotInitialized");
+    });
+
+    it("credits the referrer a share of the raffle fee", async () => {
+      await buy(referee, 2, referrer.publicKey);
+      const rewards = await program.account.referrerRewards.fetch(rewardsOf(referrer.publicKey));
+      assert.ok(rewards.pending.gtn(0));
+      assert.equal(rewards.referralCount, 1);
+      assert.equal(rewards.referredSol.toString(), String(anchor.web3.LAMPORTS_PER_SOL / 10));
+
+      const profile = await program.account.buyerProfile.fetch(profileOf(referee.publicKey));
+      assert.ok(profile.referrer.equals(referrer.publicKey));
+    });
+
+    it("keeps the referrer bound at the first buy", async () => {
+      await buy(stranger, 1, null);
+      await expectError(buy(referee, 3, stranger.publicKey), "InvalidReferrer");
+    });
+
+    it("prevents cycles back to a wallet's own referee", async () => {
+      // The referrer already bought without a referrer, so it can never bind one
+      await expectError(buy(referrer, 2, referee.publicKey), "InvalidReferrer");
+    });
+
+    it("rejects self-referral", async () => {
+      const fresh = anchor.web3.Keypair.generate();
+      await fund(provider.connection, fresh.publicKey);
+      // Give the wallet a profile without a buy, as a sell or the authority would
+      await program.methods
+        .setMarketMaker(fresh.publicKey, false)
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+      await expectError(buy(fresh, 1, fresh.publicKey), "SelfReferral");
+    });
+
+    it("pays out pending rewards on claim", async () => {
+      const before = await program.account.referrerRewards.fetch(rewardsOf(referrer.publicKey));
+      await program.methods
+        .claimReferralRewards()
+        .accountsPartial({ programState, tokenMint, referrer: referrer.publicKey })
+        .signers([referrer])
+        .rpc();
+
+      const after = await program.account.referrerRewards.fetch(rewardsOf(referrer.publicKey));
+      assert.equal(after.pending.toNumber(), 0);
+      assert.equal(after.totalClaimed.toString(), before.pending.toString());
+      await expectError(
+        program.methods
+          .claimReferralRewards()
+          .accountsPartial({ programState, tokenMint, referrer: referrer.publicKey })
+          .signers([referrer])
+          .rpc(),
+        "NothingToClaim"
+      );
+    });
+  });
+
+  describe("staking", () => {
+    const staker = anchor.web3.Keypair.generate();
+    const trader = anchor.web3.Keypair.generate();
+    let stakerTokenAccount: anchor.web3.PublicKey;
+    const [stakePool] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("stake-pool")], program.programId);
+
+    const buy = (buyer: anchor.web3.Keypair, timestamp: number) => buyTokens(buyer, anchor.web3.LAMPORTS_PER_SOL, timestamp).rpc();
+
+    const stakeAccounts = () => ({
+      programState,
+      tokenMint,
+      owner: staker.publicKey,
+      ownerTokenAccount: stakerTokenAccount,
+    });
+
+    before(async () => {
+      await fund(provider.connection, staker.publicKey);
+      await fund(provider.connection, trader.publicKey);
+      stakerTokenAccount = await anchor.utils.token.associatedAddress({ mint: tokenMint, owner: staker.publicKey });
+      await buy(staker, 1);
+    });
+
+    it("stakes tokens into the pool vault", async () => {
+      await program.methods.stake(new anchor.BN(10_000_000_000)).accountsPartial(stakeAccounts()).signers([staker]).rpc();
+      const pool = await program.account.stakePool.fetch(stakePool);
+      assert.ok(pool.totalStaked.gte(new anchor.BN(10_000_000_000)));
+    });
+
+    it("earns a share of the dev fee from other wallets' buys", async () => {
+      const before = await program.account.stakePool.fetch(stakePool);
+      await buy(trader, 1);
+      const after = await program.account.stakePool.fetch(stakePool);
+      assert.ok(after.solRewards.rewardPerToken.gt(before.solRewards.rewardPerToken));
+      assert.ok(after.tokenRewards.rewardPerToken.eq(before.tokenRewards.rewardPerToken));
+
+      const owed = (await program.account.programState.fetch(programState)).stakingSolOwed;
+      await program.methods
+        .claimStakingRewards()
+        .accountsPartial({ programState, tokenMint, owner: staker.publicKey, programVault })
+        .signers([staker])
+        .rpc();
+      const state = await program.account.programState.fetch(programState);
+      assert.ok(state.stakingSolOwed.lt(owed));
+      await expectError(
+        program.methods
+          .claimStakingRewards()
+          .accountsPartial({ programState, tokenMint, owner: staker.publicKey, programVault })
+          .signers([staker])
+          .rpc(),
+        "NothingToClaim"
+      );
+    });
+
+    it("holds unstakes until the cooldown has passed", async () => {
+      await program.methods
+        .configureStaking(50_000, new anchor.BN(3_600))
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+      await expectError(
+        program.methods.unstake(new anchor.BN(10_000_000_000)).accountsPartial(stakeAccounts()).signers([staker]).rpc(),
+        "StakeCooldown"
+      );
+
+      await program.methods
+        .configureStaking(50_000, new anchor.BN(0))
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+      await program.methods.unstake(new anchor.BN(10_000_000_000)).accountsPartial(stakeAccounts()).signers([staker]).rpc();
+    });
+
+    it("only lets the authority change the pool settings", async () => {
+      await expectError(
+        program.methods
+          .configureStaking(100_000, new anchor.BN(0))
+          .accountsPartial({ programState, authority: staker.publicKey })
+          .signers([staker])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("vesting", () => {
+    const beneficiary = anchor.web3.Keypair.generate();
+    const DAY = 24 * 60 * 60;
+    const TOTAL = 1_000_000_000_000;
+    const [vestingPool] = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("vesting-pool")], program.programId);
+    const scheduleFor = (id: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("vesting"), beneficiary.publicKey.toBuffer(), new anchor.BN(id).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+
+    const createVesting = (id: number, cliff: number, duration: number, revocable: boolean) =>
+      program.methods
+        .createVesting(beneficiary.publicKey, new anchor.BN(id), new anchor.BN(TOTAL), new anchor.BN(cliff), new anchor.BN(duration), revocable)
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+
+    const claim = (id: number) =>
+      program.methods
+        .claimVested()
+        .accountsPartial({ programState, tokenMint, beneficiary: beneficiary.publicKey, vestingSchedule: scheduleFor(id) })
+        .signers([beneficiary])
+        .rpc();
+
+    before(async () => {
+      await fund(provider.connection, beneficiary.publicKey);
+      // Room for exactly two schedules
+      await program.methods
+        .initializeVesting(new anchor.BN(2 * TOTAL))
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+    });
+
+    it("records an auditable schedule", async () => {
+      await createVesting(1, 30 * DAY, 180 * DAY, false);
+      const schedule = await program.account.vestingSchedule.fetch(scheduleFor(1));
+      assert.ok(schedule.beneficiary.equals(beneficiary.publicKey));
+      assert.equal(schedule.total.toString(), TOTAL.toString());
+      assert.equal(schedule.claimed.toNumber(), 0);
+      const pool = await program.account.vestingPool.fetch(vestingPool);
+      assert.equal(pool.totalAllocated.toString(), TOTAL.toString());
+    });
+
+    it("releases nothing before the cliff", async () => {
+      await expectError(claim(1), "NothingToClaim");
+    });
+
+    it("rejects impossible schedules", async () => {
+      await expectError(createVesting(3, 0, 0, false), "InvalidVesting");
+      await expectError(createVesting(3, 180 * DAY + 1, 180 * DAY, false), "InvalidVesting");
+    });
+
+    it("rejects schedules beyond the vesting allocation", async () => {
+      await createVesting(2, 30 * DAY, 360 * DAY, true);
+      await expectError(createVesting(3, 30 * DAY, 180 * DAY, false), "VestingAllocationExceeded");
+    });
+
+    it("lets the authority cancel unvested tokens only where allowed", async () => {
+      await program.methods
+        .revokeVesting()
+        .accountsPartial({ programState, authority: provider.wallet.publicKey, vestingSchedule: scheduleFor(2) })
+        .rpc();
+      const schedule = await program.account.vestingSchedule.fetch(scheduleFor(2));
+      assert.ok(schedule.revoked);
+      assert.equal(schedule.total.toNumber(), 0);
+      // The cancelled tokens go back to the allocation
+      const pool = await program.account.vestingPool.fetch(vestingPool);
+      assert.equal(pool.totalAllocated.toString(), TOTAL.toString());
+
+      await expectError(
+        program.methods
+          .revokeVesting()
+          .accountsPartial({ programState, authority: provider.wallet.publicKey, vestingSchedule: scheduleFor(1) })
+          .rpc(),
+        "VestingNotRevocable"
+      );
+    });
+
+    it("releases everything once the duration has passed", async () => {
+      // Uses the allocation the revoked schedule gave back
+      await createVesting(3, 0, 2, false);
+      await sleep(3_000);
+      await claim(3);
+      const schedule = await program.account.vestingSchedule.fetch(scheduleFor(3));
+      assert.equal(schedule.claimed.toString(), TOTAL.toString());
+      await expectError(claim(3), "NothingToClaim");
+    });
+
+    it("only lets the authority create schedules", async () => {
+      await expectError(
+        program.methods
+          .createVesting(beneficiary.publicKey, new anchor.BN(4), new anchor.BN(1), new anchor.BN(30 * DAY), new anchor.BN(180 * DAY), false)
+          .accountsPartial({ programState, authority: beneficiary.publicKey })
+          .signers([beneficiary])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+  });
+
+  describe("fee splitter", () => {
+    const team = anchor.web3.Keypair.generate();
+    const marketing = anchor.web3.Keypair.generate();
+    const legal = anchor.web3.Keypair.generate();
+    const buyer = anchor.web3.Keypair.generate();
+
+    const split = (shares: [anchor.web3.Keypair, number][]) =>
+      shares.map(([wallet, shareBps]) => ({ wallet: wallet.publicKey, shareBps }));
+
+    const configure = (shares: [anchor.web3.Keypair, number][]) =>
+      program.methods
+        .configureFeeSplitter(split(shares))
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+
+    const distribute = (recipients: anchor.web3.Keypair[]) =>
+      program.methods
+        .distributeFees()
+        .accountsPartial({ programState, programVault })
+        .remainingAccounts(recipients.map((wallet) => ({ pubkey: wallet.publicKey, isWritable: true, isSigner: false })))
+        .rpc();
+
+    const balancesOf = (wallets: anchor.web3.Keypair[]) =>
+      Promise.all(wallets.map((wallet) => provider.connection.getBalance(wallet.publicKey)));
+
+    before(async () => {
+      for (const wallet of [team, marketing, legal, buyer]) {
+        await fund(provider.connection, wallet.publicKey);
+      }
+
+      await buyTokens(buyer, anchor.web3.LAMPORTS_PER_SOL, 1).rpc();
+    });
+
+    it("rejects shares that do not add up to 100%", async () => {
+      await expectError(configure([[team, 50_000], [marketing, 30_000]]), "InvalidFeeSplit");
+      await expectError(configure([[team, 50_000], [team, 50_000]]), "InvalidFeeSplit");
+      await expectError(configure([[team, 100_000], [marketing, 0]]), "InvalidFeeSplit");
+    });
+
+    it("only lets the authority change the split", async () => {
+      await expectError(
+        program.methods
+          .configureFeeSplitter(split([[buyer, 100_000]]))
+          .accountsPartial({ programState, authority: buyer.publicKey })
+          .signers([buyer])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("rejects recipients passed out of order", async () => {
+      await configure([[team, 50_000], [marketing, 30_000], [legal, 20_000]]);
+      await expectError(distribute([legal, marketing, team]), "InvalidFeeSplit");
+    });
+
+    it("pays each recipient its share of the accrued dev fees", async () => {
+      const accrued = (await program.account.programState.fetch(programState)).devFeesAccrued.toNumber();
+      assert.isAbove(accrued, 0);
+      const before = await balancesOf([team, marketing, legal]);
+
+      // Only the fee payer signs; the authority is not involved
+      await distribute([team, marketing, legal]);
+
+      const after = await balancesOf([team, marketing, legal]);
+      const paid = after.map((balance, i) => balance - before[i]);
+      assert.deepEqual(paid, [
+        Math.floor((accrued * 50) / 100),
+        Math.floor((accrued * 30) / 100),
+        Math.floor((accrued * 20) / 100),
+      ]);
+      const splitter = await program.account.feeSplitter.fetch(
+        anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("fee-splitter")], program.programId)[0]
+      );
+      assert.equal(splitter.totalDistributed.toNumber(), paid.reduce((a, b) => a + b, 0));
+    });
+  });
+
+  describe("buyback and burn", () => {
+    const cranker = anchor.web3.Keypair.generate();
+    const trader = anchor.web3.Keypair.generate();
+    const cap = 50_000;
+
+    const buyback = () =>
+      program.methods
+        .buybackAndBurn()
+        .accountsPartial({ programState, tokenMint, programVault })
+        .signers([cranker])
+        .rpc();
+
+    before(async () => {
+      await fund(provider.connection, cranker.publicKey);
+      await fund(provider.connection, trader.publicKey);
+    });
+
+    it("does nothing until the authority enables it", async () => {
+      await expectError(buyback(), "NothingToBuyBack");
+      await expectError(
+        program.methods
+          .configureBuyback(50_000, new anchor.BN(0), new anchor.BN(3_600), 100_000)
+          .accountsPartial({ programState, authority: cranker.publicKey })
+          .signers([cranker])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("sets aside a share of each buy's dev fee", async () => {
+      // All of the surplus, capped per call, funded by all of the dev fee left after stakers
+      await program.methods
+        .configureBuyback(100_000, new anchor.BN(cap), new anchor.BN(3_600), 100_000)
+        .accountsPartial({ programState, authority: provider.wallet.publicKey })
+        .rpc();
+      const before = await program.account.programState.fetch(programState);
+
+      await buyTokens(trader, anchor.web3.LAMPORTS_PER_SOL, 1).rpc();
+
+      const after = await program.account.programState.fetch(programState);
+      assert.isTrue(after.buyback.accrued.gtn(cap));
+      // None of it is withdrawable as dev fees
+      assert.isTrue(after.devFeesAccrued.eq(before.devFeesAccrued));
+    });
+
+    it("burns tokens bought with the fees set aside", async () => {
+      const before = await program.account.programState.fetch(programState);
+
+      await buyback();
+
+      const after = await program.account.programState.fetch(programState);
+      // The fees set aside would cover more; the per-call cap wins
+      assert.equal(after.solReserve.sub(before.solReserve).toNumber(), cap);
+      assert.equal(before.buyback.accrued.sub(after.buyback.accrued).toNumber(), cap);
+      const bought = after.totalBoughtBack.sub(before.totalBoughtBack);
+      assert.isTrue(bought.gtn(0));
+      assert.isTrue(after.totalBurned.sub(before.totalBurned).eq(bought));
+      assert.isTrue(after.currentPrice.gt(before.currentPrice));
+    });
+
+    it("waits out the minimum interval between calls", async () => {
+      await expectError(buyback(), "BuybackTooSoon");
+    });
+  });
+
+  describe("supply accounting", () => {
+    it("reconciles the ledger with the mint supply", async () => {
+      const report = await program.methods.reconcileSupply().accountsPartial({ programState, tokenMint }).view();
+      const mint = await new Token(provider.connection, tokenMint, TOKEN_PROGRAM_ID, provider.wallet.payer).getMintInfo();
+
+      assert.ok(report.reconciled);
+      assert.equal(report.mintSupply.toString(), mint.supply.toString());
+      assert.equal(
+        report.circulating.toString(),
+        report.ledger.initialSupply.add(report.ledger.minted).sub(report.ledger.explicitBurned).toString()
+      );
+      // Earlier trades both minted and burned
+      assert.isTrue(report.ledger.minted.gtn(0));
+      assert.isTrue(report.ledger.implicitBurned.gtn(0));
+      assert.isTrue(report.ledger.explicitBurned.gtn(0));
+    });
+  });
+
+  describe("protocol stats", () => {
+    const buyer = anchor.web3.Keypair.generate();
+    const statsPda = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("stats")], program.programId)[0];
+
+    const buy = (timestamp: number) => buyTokens(buyer, anchor.web3.LAMPORTS_PER_SOL / 10, timestamp).rpc();
+
+    before(async () => {
+      await fund(provider.connection, buyer.publicKey);
+    });
+
+    it("counts volume on every buy and a new buyer only once", async () => {
+      const before = await program.account.protocolStats.fetch(statsPda);
+      await buy(1);
+      await buy(2);
+      const after = await program.account.protocolStats.fetch(statsPda);
+
+      assert.equal(after.buyCount.sub(before.buyCount).toNumber(), 2);
+      assert.equal(after.uniqueBuyers.sub(before.uniqueBuyers).toNumber(), 1);
+      assert.equal(after.solIn.sub(before.solIn).toNumber(), anchor.web3.LAMPORTS_PER_SOL / 5);
+    });
+
+    it("tracks the all-time high and low price", async () => {
+      const stats = await program.account.protocolStats.fetch(statsPda);
+      const state = await program.account.programState.fetch(programState);
+      assert.isTrue(stats.allTimeHigh.gte(state.currentPrice));
+      assert.isTrue(stats.allTimeLow.lte(state.currentPrice));
+      // Earlier sells paid out SOL
+      assert.isTrue(stats.sellCount.gtn(0));
+      assert.isTrue(stats.solOut.gtn(0));
+    });
+  });
+
+  describe("price oracle", () => {
+    const priceOracle = anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("price-oracle")], program.programId)[0];
+    const twap = (window: number) => program.methods.getTwap(new anchor.BN(window)).accountsPartial({ priceOracle }).view();
+
+    it("records an observation for every trade", async () => {
+      const oracle = await program.account.priceOracle.fetch(priceOracle);
+      const state = await program.account.programState.fetch(programState);
+      assert.isAbove(oracle.ring.len, 0);
+      assert.equal(oracle.ring.lastPrice.toString(), state.currentPrice.toString());
+    });
+
+    it("returns a time-weighted price within the traded range", async () => {
+      await sleep(2_000);
+      const price = await twap(1);
+      const stats = await program.account.protocolStats.fetch(
+        anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("stats")], program.programId)[0]
+      );
+      assert.isTrue(price.gte(stats.allTimeLow));
+      assert.isTrue(price.lte(stats.allTimeHigh));
+    });
+
+    it("rejects windows the history does not cover", async () => {
+      await expectError(twap(0), "InvalidTwapWindow");
+      await expectError(twap(7 * 24 * 60 * 60), "InvalidTwapWindow");
+    });
+  });
+
+  describe("limit buys", () => {
+    const owner = anchor.web3.Keypair.generate();
+    const cranker = anchor.web3.Keypair.generate();
+    const solAmount = new anchor.BN(anchor.web3.LAMPORTS_PER_SOL / 10);
+
+    const orderFor = (id: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("limit-buy"), owner.publicKey.toBuffer(), new anchor.BN(id).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+
+    const place = async (id: number, targetPrice: anchor.BN, expiresIn = 3_600) =>
+      program.methods
+        .placeLimitBuy(new anchor.BN(id), solAmount, targetPrice, new anchor.BN(Math.floor(Date.now() / 1000) + expiresIn))
+        .accountsPartial({ programState, tokenMint, owner: owner.publicKey, limitOrder: orderFor(id) })
+        .signers([owner])
+        .rpc();
+
+    const execute = (id: number, timestamp: number) => {
+      const ts = new anchor.BN(timestamp);
+      const purchaseOrder = purchaseOrderFor(owner.publicKey, timestamp);
+      return program.methods
+        .executeLimitBuy(ts)
+        .accountsPartial({
+          programState,
+          tokenMint,
+          cranker: cranker.publicKey,
+          owner: owner.publicKey,
+          limitOrder: orderFor(id),
+          purchaseOrder,
+          programVault,
+        })
+        .signers([cranker])
+        .rpc();
+    };
+
+    before(async () => {
+      await fund(provider.connection, owner.publicKey);
+      await fund(provider.connection, cranker.publicKey);
+    });
+
+    it("escrows the SOL until the order is cancelled", async () => {
+      await place(1, new anchor.BN(1));
+      const escrowed = await provider.connection.getBalance(orderFor(1));
+      assert.isAbove(escrowed, solAmount.toNumber());
+
+      const before = await provider.connection.getBalance(owner.publicKey);
+      await program.methods
+        .cancelLimitBuy()
+        .accountsPartial({ owner: owner.publicKey, limitOrder: orderFor(1) })
+        .signers([owner])
+        .rpc();
+      assert.isNull(await provider.connection.getAccountInfo(orderFor(1)));
+      assert.isAbove(await provider.connection.getBalance(owner.publicKey), before + solAmount.toNumber());
+    });
+
+    it("waits while the price is above the target", async () => {
+      await place(2, new anchor.BN(1));
+      await expectError(execute(2, 1), "LimitPriceNotReached");
+    });
+
+    it("fills through the buy path and pays the crank", async () => {
+      const state = await program.account.programState.fetch(programState);
+      await place(3, state.currentPrice.muln(2));
+      const crankerBefore = await provider.connection.getBalance(cranker.publicKey);
+
+      await execute(3, 2);
+
+      const order = await program.account.purchaseOrder.fetch(purchaseOrderFor(owner.publicKey, 2));
+      assert.ok(order.buyer.equals(owner.publicKey));
+      assert.equal(order.solAmount.toString(), solAmount.toString());
+      assert.isNull(await provider.connection.getAccountInfo(orderFor(3)));
+      // Rent refunded plus the reward, less the transaction fee
+      assert.isAbove(await provider.connection.getBalance(cranker.publicKey), crankerBefore + 100_000 - 10_000);
+    });
+
+    it("rejects expired orders", async () => {
+      await place(4, new anchor.BN("340282366920938463463374607431768211455"), 2);
+      await sleep(3_000);
+      await expectError(execute(4, 3), "OrderExpired");
+    });
+  });
+
+  describe("dca", () => {
+    const owner = anchor.web3.Keypair.generate();
+    const cranker = anchor.web3.Keypair.generate();
+    const amountPerInterval = new anchor.BN(anchor.web3.LAMPORTS_PER_SOL / 20);
+
+    const scheduleFor = (id: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("dca"), owner.publicKey.toBuffer(), new anchor.BN(id).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+
+    const create = (id: number, interval: number, intervals: number) =>
+      program.methods
+        .createDca(new anchor.BN(id), amountPerInterval, new anchor.BN(interval), intervals)
+        .accountsPartial({ programState, tokenMint, owner: owner.publicKey, dcaSchedule: scheduleFor(id) })
+        .signers([owner])
+        .rpc();
+
+    const execute = (id: number, timestamp: number) => {
+      const ts = new anchor.BN(timestamp);
+      const purchaseOrder = purchaseOrderFor(owner.publicKey, timestamp);
+      return program.methods
+        .executeDca(ts)
+        .accountsPartial({
+          programState,
+          tokenMint,
+          cranker: cranker.publicKey,
+          owner: owner.publicKey,
+          dcaSchedule: scheduleFor(id),
+          purchaseOrder,
+          programVault,
+        })
+        .signers([cranker])
+        .rpc();
+    };
+
+    before(async () => {
+      await fund(provider.connection, owner.publicKey);
+      await fund(provider.connection, cranker.publicKey);
+    });
+
+    it("buys one instalment per interval", async () => {
+      await create(1, 3_600, 2);
+      await execute(1, 101);
+
+      const schedule = await program.account.dcaSchedule.fetch(scheduleFor(1));
+      assert.equal(schedule.intervalsRemaining, 1);
+      const order = await program.account.purchaseOrder.fetch(purchaseOrderFor(owner.publicKey, 101));
+      assert.ok(order.buyer.equals(owner.publicKey));
+      assert.equal(order.solAmount.toString(), amountPerInterval.toString());
+
+      await expectError(execute(1, 102), "DcaNotDue");
+    });
+
+    it("closes the schedule after the last instalment", async () => {
+      await create(2, 1, 1);
+      await execute(2, 103);
+      assert.isNull(await provider.connection.getAccountInfo(scheduleFor(2)));
+    });
+
+    it("refunds the unspent deposit when stopped", async () => {
+      await create(3, 3_600, 3);
+      await execute(3, 104);
+      const remaining = await provider.connection.getBalance(scheduleFor(3));
+      const before = await provider.connection.getBalance(owner.publicKey);
+
+      await program.methods
+        .stopDca()
+        .accountsPartial({ owner: owner.publicKey, dcaSchedule: scheduleFor(3) })
+        .signers([owner])
+        .rpc();
+
+      assert.isNull(await provider.connection.getAccountInfo(scheduleFor(3)));
+      assert.isAbove(await provider.connection.getBalance(owner.publicKey), before + remaining - 10_000);
+    });
+  });
+
+  describe("gift buys", () => {
+    const payer = anchor.web3.Keypair.generate();
+    const recipient = anchor.web3.Keypair.generate();
+    const profileOf = (wallet: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("buyer-profile"), wallet.toBuffer()], program.programId)[0];
+
+    const allowanceFor = (beneficiary: anchor.web3.PublicKey) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("gift-allowance"), beneficiary.toBuffer(), payer.publicKey.toBuffer()],
+        program.programId
+      )[0];
+
+    const buyFor = async (beneficiary: anchor.web3.PublicKey, timestamp: number, referrer?: anchor.web3.PublicKey) => {
+      // Passed only when it exists, so missing allowances reach the program's check
+      const allowance = allowanceFor(beneficiary);
+      const giftAllowance = (await provider.connection.getAccountInfo(allowance)) ? allowance : null;
+      return buyTokens(payer, anchor.web3.LAMPORTS_PER_SOL / 10, timestamp, {
+        beneficiary,
+        referrerProfile: referrer && profileOf(referrer),
+        referrerRewards:
+          referrer &&
+          anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("referrer-rewards"), referrer.toBuffer()], program.programId)[0],
+        giftAllowance,
+      }).rpc();
+    };
+
+    const allowGifts = (beneficiary: anchor.web3.Keypair) =>
+      program.methods
+        .allowGifts(payer.publicKey)
+        .accountsPartial({ beneficiary: beneficiary.publicKey })
+        .signers([beneficiary])
+        .rpc();
+
+    before(async () => {
+      await fund(provider.connection, payer.publicKey);
+      await fund(provider.connection, recipient.publicKey);
+      await allowGifts(recipient);
+    });
+
+    it("rejects gifts the beneficiary has not allowed", async () => {
+      const stranger = anchor.web3.Keypair.generate();
+      await expectError(buyFor(stranger.publicKey, 1), "GiftNotAllowed");
+    });
+
+    it("sends the tokens and the order to the beneficiary", async () => {
+      const recipientBefore = await provider.connection.getBalance(recipient.publicKey);
+      await buyFor(recipient.publicKey, 1);
+
+      const order = await program.account.purchaseOrder.fetch(purchaseOrderFor(recipient.publicKey, 1));
+      assert.ok(order.buyer.equals(recipient.publicKey));
+      const recipientTokens = await anchor.utils.token.associatedAddress({ mint: tokenMint, owner: recipient.publicKey });
+      const balance = await provider.connection.getTokenAccountBalance(recipientTokens);
+      assert.equal(balance.value.amount, order.tokenAmount.toString());
+      // The payer funded everything
+      assert.equal(await provider.connection.getBalance(recipient.publicKey), recipientBefore);
+    });
+
+    it("can't bind a referrer for someone else", async () => {
+      await buyFor(payer.publicKey, 2);
+      await expectError(buyFor(recipient.publicKey, 3, payer.publicKey), "InvalidReferrer");
+    });
+
+    it("stops gifts once the allowance is revoked", async () => {
+      await program.methods
+        .revokeGiftAllowance()
+        .accountsPartial({ beneficiary: recipient.publicKey, giftAllowance: allowanceFor(recipient.publicKey) })
+        .signers([recipient])
+        .rpc();
+      await expectError(buyFor(recipient.publicKey, 4), "GiftNotAllowed");
+    });
+  });
+
+  describe("exact-output buys", () => {
+    const buyer = anchor.web3.Keypair.generate();
+    // The airdrop eligibility threshold, 10,000 tokens
+    const tokenAmount = new anchor.BN(10_000).mul(new anchor.BN(10).pow(new anchor.BN(9)));
+
+    const buyExact = (timestamp: number, maxSolIn: anchor.BN) => {
+      const ts = new anchor.BN(timestamp);
+      const purchaseOrder = purchaseOrderFor(buyer.publicKey, timestamp);
+      return {
+        purchaseOrder,
+        rpc: () =>
+          program.methods
+            .buyExactTokens(tokenAmount, ts, maxSolIn)
+            .accountsPartial({ programState, tokenMint, buyer: buyer.publicKey, beneficiary: buyer.publicKey, purchaseOrder, programVault })
+            .signers([buyer])
+            .rpc(),
+      };
+    };
+
+    before(async () => {
+      await fund(provider.connection, buyer.publicKey);
+    });
+
+    it("rejects a buy that costs more than the maximum", async () => {
+      await expectError(buyExact(1, new anchor.BN(1)).rpc(), "SlippageExceeded");
+    });
+
+    it("delivers at least the requested tokens", async () => {
+      const { purchaseOrder, rpc } = buyExact(2, new anchor.BN(anchor.web3.LAMPORTS_PER_SOL));
+      await rpc();
+      const order = await program.account.purchaseOrder.fetch(purchaseOrder);
+      assert.ok(order.tokenAmount.gte(tokenAmount));
+      assert.isAtMost(order.solAmount.toNumber(), anchor.web3.LAMPORTS_PER_SOL);
+    });
+  });
+
+  describe("sell orders", () => {
+    const owner = anchor.web3.Keypair.generate();
+    const cranker = anchor.web3.Keypair.generate();
+    const tokenAmount = new anchor.BN(1_000_000_000);
+    let ownerTokenAccount: anchor.web3.PublicKey;
+
+    const orderFor = (id: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("sell-order"), owner.publicKey.toBuffer(), new anchor.BN(id).toArrayLike(Buffer, "le", 8)],
+        program.programId
+      )[0];
+    const escrowFor = (id: number) =>
+      anchor.web3.PublicKey.findProgramAddressSync([Buffer.from("sell-order-escrow"), orderFor(id).toBuffer()], program.programId)[0];
+
+    const place = (id: number, triggerPrice: anchor.BN, direction: object, minSolOut = 0, expiresIn = 3_600) =>
+      program.methods
+        .placeSellOrder(
+          new anchor.BN(id),
+          tokenAmount,
+          triggerPrice,
+          direction as any,
+          new anchor.BN(minSolOut),
+          new anchor.BN(Math.floor(Date.now() / 1000) + expiresIn)
+        )
+        .accountsPartial({ programState, tokenMint, owner: owner.publicKey, ownerTokenAccount, sellOrder: orderFor(id), escrow: escrowFor(id) })
+        .signers([owner])
+        .rpc();
+
+    const execute = (id: number) =>
+      program.methods
+        .executeSellOrder()
+        .accountsPartial({
+          programState,
+          tokenMint,
+          cranker: cranker.publicKey,
+          owner: owner.publicKey,
+          sellOrder: orderFor(id),
+          escrow: escrowFor(id),
+          ownerTokenAccount,
+          programVault,
+        })
+        .signers([cranker])
+        .rpc();
+
+    const balance = async () => (await provider.connection.getTokenAccountBalance(ownerTokenAccount)).value.amount;
+
+    before(async () => {
+      await fund(provider.connection, owner.publicKey);
+      await fund(provider.connection, cranker.publicKey);
+      ownerTokenAccount = await anchor.utils.token.associatedAddress({ mint: tokenMint, owner: owner.publicKey });
+
+      await buyTokens(owner, anchor.web3.LAMPORTS_PER_SOL, 1).rpc();
+      await sleep(2_000);
+    });
+
+    it("escrows tokens until the order is cancelled", async () => {
+      const before = await balance();
+      await place(1, new anchor.BN(1), { stopLoss: {} });
+      assert.equal((await provider.connection.getTokenAccountBalance(escrowFor(1))).value.amount, tokenAmount.toString());
+
+      await program.methods
+        .cancelSellOrder()
+        .accountsPartial({ owner: owner.publicKey, sellOrder: orderFor(1), escrow: escrowFor(1), ownerTokenAccount })
+        .signers([owner])
+        .rpc();
+      assert.equal(await balance(), before);
+      assert.isNull(await provider.connection.getAccountInfo(escrowFor(1)));
+    });
+
+    it("waits until the price crosses the trigger", async () => {
+      await place(2, new anchor.BN(1), { stopLoss: {} });
+      await expectError(execute(2), "SellOrderNotTriggered");
+    });
+
+    it("rejects fills below the owner's minimum", async () => {
+      await place(3, new anchor.BN(1), { takeProfit: {} }, 1_000 * anchor.web3.LAMPORTS_PER_SOL);
+      await expectError(execute(3), "SlippageExceeded");
+    });
+
+    it("sells a triggered order and pays the owner and the crank", async () => {
+      await place(4, new anchor.BN(1), { takeProfit: {} });
+      const ownerBefore = await provider.connection.getBalance(owner.publicKey);
+
+      await execute(4);
+
+      assert.isNull(await provider.connection.getAccountInfo(orderFor(4)));
+      assert.isNull(await provider.connection.getAccountInfo(escrowFor(4)));
+      assert.isAbove(await provider.connection.getBalance(owner.publicKey), ownerBefore);
+    });
+
+    it("returns the tokens of an expired order", async () => {
+      await place(5, new anchor.BN(1), { takeProfit: {} }, 0, 2);
+      const before = await balance();
+      await sleep(3_000);
+
+      await execute(5);
+
+      assert.equal(new anchor.BN(await balance()).sub(new anchor.BN(before)).toString(), tokenAmount.toString());
+      assert.isNull(await provider.connection.getAccountInfo(orderFor(5)));
+    });
+  });
+
+  describe("account constraints", () => {
+    const attacker = anchor.web3.Keypair.generate();
+    let foreignMint: Token;
+    let attackerForeignAccount: anchor.web3.PublicKey;
+    let lastAirdrop: anchor.web3.PublicKey;
+
+    before(async () => {
+      await fund(provider.connection, attacker.publicKey);
+
+      // A mint the attacker fully controls, with tokens they can burn at will
+      foreignMint = await Token.createMint(
+        provider.connection,
+        attacker,
+        attacker.publicKey,
+        null,
+        9,
+        TOKEN_PROGRAM_ID
+      );
+      attackerForeignAccount = await foreignMint.createAccount(attacker.publicKey);
+      await foreignMint.mintTo(attackerForeignAccount, attacker, [], 1_000_000_000_000);
+
+      [lastAirdrop] = anchor.web3.PublicKey.findProgramAddressSync(
+        [Buffer.from("last-airdrop")],
+        program.programId
+      );
+    });
+
+    it("rejects sells of a foreign mint", async () => {
+      await expectError(
+        program.methods
+          .sellTokens(new anchor.BN(1_000_000_000))
+          .accountsPartial({
+            programState,
+            tokenMint: foreignMint.publicKey,
+            seller: attacker.publicKey,
+            sellerTokenAccount: attackerForeignAccount,
+            programVault,
+          })
+          .signers([attacker])
+          .rpc(),
+        "InvalidMint"
+      );
+    });
+
+    it("rejects sells from a foreign-mint token account", async () => {
+      await expectError(
+        program.methods
+          .sellTokens(new anchor.BN(1_000_000_000))
+          .accountsPartial({
+            programState,
+            tokenMint,
+            seller: attacker.publicKey,
+            sellerTokenAccount: attackerForeignAccount,
+            programVault,
+          })
+          .signers([attacker])
+          .rpc(),
+        "InvalidMint"
+      );
+    });
+
+    it("rejects sells from someone else's token account", async () => {
+      const mint = new Token(provider.connection, tokenMint, TOKEN_PROGRAM_ID, provider.wallet.payer);
+      const victimAccount = await mint.createAccount(provider.wallet.publicKey);
+
+      await expectError(
+        program.methods
+          .sellTokens(new anchor.BN(1_000_000_000))
+          .accountsPartial({
+            programState,
+            tokenMint,
+            seller: attacker.publicKey,
+            sellerTokenAccount: victimAccount,
+            programVault,
+          })
+          .signers([attacker])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("rejects airdrops to a foreign-mint token account", async () => {
+      await expectError(
+        program.methods
+          .airdrop()
+          .accountsPartial({
+            programState,
+            tokenMint,
+            authority: provider.wallet.publicKey,
+            recipientTokenAccount: attackerForeignAccount,
+            lastAirdrop,
+          })
+          .rpc(),
+        "InvalidMint"
+      );
+    });
+
+    it("rejects airdrops signed by anyone but the authority", async () => {
+      await expectError(
+        program.methods
+          .airdrop()
+          .accountsPartial({
+            programState,
+            tokenMint,
+            authority: attacker.publicKey,
+            recipientTokenAccount: attackerForeignAccount,
+            lastAirdrop,
+          })
+          .signers([attacker])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("rejects settings changes from anyone but the authority", async () => {
+      await expectError(
+        program.methods
+          .updateAirdropSettings(new anchor.BN(1))
+          .accountsPartial({ programState, authority: attacker.publicKey })
+          .signers([attacker])
+          .rpc(),
+        "Unauthorized"
+      );
+    });
+
+    it("rejects switching to a mint the program cannot mint", async () => {
+      await expectError(
+        program.methods
+          .updateTokenMint()
+          .accountsPartial({
+            programState,
+            authority: provider.wallet.publicKey,
+            newTokenMint: foreignMint.publicKey,
+          })
+          .rpc(),
+        "InvalidMint"
+      );
+    });
+
+    it("rejects a program state that is not the canonical PDA", async () => {
+      await expectError(
+        program.methods
+          .updateAirdropSettings(new anchor.BN(1))
+          .accountsPartial({ programState: lastAirdrop, authority: provider.wallet.publicKey })
+          .rpc(),
+        "AccountDiscriminatorMismatch"
+      );
+    });
+  });
 });
 
+// Helper to fund a fresh keypair on the local validator
+async function fund(connection: anchor.web3.Connection, to: anchor.web3.PublicKey) {
+  const signature = await connection.requestAirdrop(to, 2 * anchor.web3.LAMPORTS_PER_SOL);
+  await connection.confirmTransaction(signature, "confirmed");
+}
+
+const sleep = (ms: number) => new Promise((resolve) => setTimeout(resolve, ms));
+
+// Helper to assert a transaction fails with the given Anchor error code
+async function expectError(tx: Promise<unknown>, code: string) {
+  try {
+    await tx;
+  } catch (err) {
+    assert.include(String(err), code);
+    return;
+  }
+  assert.fail(`Expected ${code}`);
+}
+
 // Helper to create mint
 async function createMint(
   connection: anchor.web3.Connection,
@@ -101,7 +1456,13 @@ async function createMint(
   freezeAuthority: anchor.web3.PublicKey | null,
   decimals: number
 ): Promise<anchor.web3.PublicKey> {
-  const mint = anchor.web3.Keypair.generate();
-  // Implementation would use SPL Token program
+  const mint = await Token.createMint(
+    connection,
+    payer,
+    mintAuthority,
+    freezeAuthority,
+    decimals,
+    TOKEN_PROGRAM_ID
+  );
   return mint.publicKey;
 }
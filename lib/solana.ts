@@ -153,8 +153,8 @@ export class FossrService {
       offset += 32
       offset += 32
 
-      // current_price (8), total_burned (8), total_buys (8)
-      offset += 8
+      // current_price (u128 fixed-point), total_burned (8), total_buys (8)
+      offset += 16
       offset += 8
       const totalBuys = this.readU64LE(data, offset); offset += 8
 
@@ -646,6 +646,13 @@ export class FossrService {
           [Buffer.from('order'), walletAddress.toBuffer(), timestampLe],
           this.programId
         )
+        const [buyerProfilePda] = PublicKey.findProgramAddressSync(
+          [Buffer.from('buyer-profile'), walletAddress.toBuffer()],
+          this.programId
+        )
+        const [stakePoolPda] = PublicKey.findProgramAddressSync([Buffer.from('stake-pool')], this.programId)
+        const [statsPda] = PublicKey.findProgramAddressSync([Buffer.from('stats')], this.programId)
+        const [priceOraclePda] = PublicKey.findProgramAddressSync([Buffer.from('price-oracle')], this.programId)
 
         // Anchor discriminator for "global:buy_tokens"
         const BUY_TOKENS_DISCRIMINATOR = Buffer.from([189, 21, 230, 133, 247, 2, 110, 42])
@@ -657,6 +664,7 @@ export class FossrService {
         ])
 
         const slotHashesSysvar = new PublicKey('SysvarS1otHashes111111111111111111111111111')
+        const instructionsSysvar = new PublicKey('Sysvar1nstructions1111111111111111111111111')
 
         const ix = new TransactionInstruction({
           programId: this.programId,
@@ -664,13 +672,23 @@ export class FossrService {
             { pubkey: programStatePda, isSigner: false, isWritable: true },
             { pubkey: this.tokenMint, isSigner: false, isWritable: true },
             { pubkey: walletAddress, isSigner: true, isWritable: true },
+            // Beneficiary: the wallet buys for itself
+            { pubkey: walletAddress, isSigner: false, isWritable: false },
             { pubkey: buyerTokenAccount, isSigner: false, isWritable: true },
             { pubkey: purchaseOrderPda, isSigner: false, isWritable: true },
+            { pubkey: buyerProfilePda, isSigner: false, isWritable: true },
+            { pubkey: stakePoolPda, isSigner: false, isWritable: true },
+            { pubkey: statsPda, isSigner: false, isWritable: true },
+            { pubkey: priceOraclePda, isSigner: false, isWritable: true },
             { pubkey: programVaultPda, isSigner: false, isWritable: true },
             { pubkey: slotHashesSysvar, isSigner: false, isWritable: false },
+            { pubkey: instructionsSysvar, isSigner: false, isWritable: false },
             { pubkey: TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
             { pubkey: ASSOCIATED_TOKEN_PROGRAM_ID, isSigner: false, isWritable: false },
             { pubkey: SystemProgram.programId, isSigner: false, isWritable: false },
+            // No referrer: optional referrer_profile and referrer_rewards are passed as the program id
+            { pubkey: this.programId, isSigner: false, isWritable: false },
+            { pubkey: this.programId, isSigner: false, isWritable: false },
           ],
           data,
         })